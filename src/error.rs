@@ -1,7 +1,11 @@
 use failure::Fail;
-use rusoto_core::RusotoError;
-use rusoto_lambda::ListFunctionsError;
+use rusoto_core::{CredentialsError, RusotoError};
+use rusoto_lambda::{
+    DeleteFunctionError, GetFunctionError, ListAliasesError, ListFunctionsError,
+    ListLayerVersionsError, ListVersionsByFunctionError,
+};
 use rusoto_resourcegroupstaggingapi::GetResourcesError;
+use std::io;
 
 /// Failure types
 #[derive(Fail, Debug)]
@@ -9,17 +13,170 @@ pub enum Error {
     #[fail(display = "{}", _0)]
     Listing(#[cause] RusotoError<ListFunctionsError>),
     #[fail(display = "{}", _0)]
+    Function(#[cause] RusotoError<GetFunctionError>),
+    #[fail(display = "{}", _0)]
+    Aliases(#[cause] RusotoError<ListAliasesError>),
+    #[fail(display = "{}", _0)]
+    Versions(#[cause] RusotoError<ListVersionsByFunctionError>),
+    #[fail(display = "{}", _0)]
+    LayerVersions(#[cause] RusotoError<ListLayerVersionsError>),
+    #[fail(display = "{}", _0)]
+    Delete(#[cause] RusotoError<DeleteFunctionError>),
+    #[fail(
+        display = "refusing to delete {} version {}, only published versions can be purged",
+        _0, _1
+    )]
+    UnsafeDelete(String, String),
+    #[fail(
+        display = "no usable AWS credentials: {}\nexport AWS_PROFILE or pass --profile to pick a profile from your credentials file, or raise --credential-timeout-ms if relying on an instance role",
+        _0
+    )]
+    Credentials(CredentialsError),
+    #[fail(
+        display = "AWS credentials expire in {}s, sooner than --min-credential-ttl allows, refresh the session before a long run",
+        _0
+    )]
+    CredentialsExpiring(i64),
+    #[fail(display = "function not found: {}", _0)]
+    NotFound(String),
+    #[fail(display = "{}", _0)]
     Tags(#[cause] RusotoError<GetResourcesError>),
+    #[fail(display = "{}", _0)]
+    Pattern(#[cause] regex::Error),
+    #[fail(
+        display = "gave up listing {} api results after {} pages, the api kept returning a next page token",
+        _0, _1
+    )]
+    Pagination(&'static str, usize),
+    #[fail(display = "failed to open output file {}: {}", _0, _1)]
+    OutputFile(String, io::Error),
+    #[fail(display = "failed to load config file {}", _0)]
+    Config(String),
+    #[fail(display = "failed to load tags file {}", _0)]
+    TagsFile(String),
+    #[fail(display = "failed to load ARNs file {}", _0)]
+    ArnsFile(String),
+    #[fail(display = "invalid region {}, expected a name like us-east-1", _0)]
+    InvalidRegion(String),
+    #[fail(display = "--fips needs a region, pass --region or set AWS_REGION")]
+    FipsRegionRequired,
+    #[fail(
+        display = "no FIPS endpoints in region {}, use one of us-east-1, us-east-2, us-west-1, us-west-2, us-gov-east-1 or us-gov-west-1",
+        _0
+    )]
+    Fips(String),
+    #[fail(display = "failed to load fixture file {}: {}", _0, _1)]
+    Fixture(String, String),
+    #[fail(display = "failed to load snapshot file {}: {}", _0, _1)]
+    Snapshot(String, String),
+    #[fail(
+        display = "{} needs the AWS APIs and can't run against a fixture or snapshot file",
+        _0
+    )]
+    FixtureUnsupported(&'static str),
+    #[fail(display = "timed out, the command took longer than --timeout allows")]
+    TimedOut,
+    #[fail(display = "interrupted, cancelled requests in flight")]
+    Interrupted,
+    #[fail(display = "failed to list lambdas in every region")]
+    AllRegions(Vec<(String, Error)>),
+}
+
+impl Error {
+    /// Return the AWS request id of a failed API call, to quote in support cases
+    ///
+    /// rusoto only keeps the response headers carrying it for errors it couldn't match to a
+    /// modeled service error, e.g. access denied, so other failures have none
+    pub fn request_id(&self) -> Option<String> {
+        match self {
+            Error::Listing(err) => request_id(err),
+            Error::Function(err) => request_id(err),
+            Error::Aliases(err) => request_id(err),
+            Error::Versions(err) => request_id(err),
+            Error::LayerVersions(err) => request_id(err),
+            Error::Delete(err) => request_id(err),
+            Error::Tags(err) => request_id(err),
+            _ => None,
+        }
+    }
+}
+
+/// Return the request id header of an unrecognized API error response
+fn request_id<E>(err: &RusotoError<E>) -> Option<String> {
+    match err {
+        RusotoError::Unknown(response) => ["x-amzn-requestid", "x-amz-request-id"]
+            .iter()
+            .find_map(|name| response.headers.get(*name))
+            .cloned(),
+        _ => None,
+    }
 }
 
 impl From<RusotoError<ListFunctionsError>> for Error {
     fn from(err: RusotoError<ListFunctionsError>) -> Self {
-        Error::Listing(err)
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::Listing(err),
+        }
+    }
+}
+
+impl From<RusotoError<GetFunctionError>> for Error {
+    fn from(err: RusotoError<GetFunctionError>) -> Self {
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::Function(err),
+        }
+    }
+}
+
+impl From<RusotoError<ListAliasesError>> for Error {
+    fn from(err: RusotoError<ListAliasesError>) -> Self {
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::Aliases(err),
+        }
+    }
+}
+
+impl From<RusotoError<ListVersionsByFunctionError>> for Error {
+    fn from(err: RusotoError<ListVersionsByFunctionError>) -> Self {
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::Versions(err),
+        }
+    }
+}
+
+impl From<RusotoError<ListLayerVersionsError>> for Error {
+    fn from(err: RusotoError<ListLayerVersionsError>) -> Self {
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::LayerVersions(err),
+        }
+    }
+}
+
+impl From<RusotoError<DeleteFunctionError>> for Error {
+    fn from(err: RusotoError<DeleteFunctionError>) -> Self {
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::Delete(err),
+        }
     }
 }
 
 impl From<RusotoError<GetResourcesError>> for Error {
     fn from(err: RusotoError<GetResourcesError>) -> Self {
-        Error::Tags(err)
+        match err {
+            RusotoError::Credentials(err) => Error::Credentials(err),
+            err => Error::Tags(err),
+        }
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Error::Pattern(err)
     }
 }
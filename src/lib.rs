@@ -0,0 +1,6753 @@
+//! AWS Lambda stock management
+//!
+//! Fetches lambda configurations and their tags, and renders them for humans and machines.
+//! The `lambstock` command line interface is a thin wrapper over this crate
+
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat, Utc};
+use colored::{ColoredString, Colorize};
+use futures::{
+    future::{self, Future},
+    stream::{self, Stream},
+    Async, Poll,
+};
+use futures_backoff::Strategy;
+use humansize::{file_size_opts as options, FileSize};
+use indicatif::ProgressBar;
+use regex::Regex;
+use rusoto_core::{
+    credential::{AutoRefreshingProvider, AwsCredentials, ChainProvider},
+    request::HttpClient,
+    CredentialsError, ProvideAwsCredentials, Region, RusotoError,
+};
+use rusoto_lambda::{
+    AliasConfiguration, DeleteFunctionError, DeleteFunctionRequest, FunctionConfiguration,
+    GetFunctionError, GetFunctionRequest, GetFunctionResponse, Lambda, LambdaClient,
+    ListAliasesError, ListAliasesRequest, ListFunctionsError, ListFunctionsRequest,
+    ListLayerVersionsError, ListLayerVersionsRequest, ListVersionsByFunctionError,
+    ListVersionsByFunctionRequest, VpcConfigResponse,
+};
+use rusoto_resourcegroupstaggingapi::{
+    GetResourcesError, GetResourcesInput, ResourceGroupsTaggingApi, ResourceGroupsTaggingApiClient,
+    ResourceTagMapping, Tag, TagFilter,
+};
+use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    cmp,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tabwriter::TabWriter;
+use tokio_sync::semaphore::{Permit, Semaphore};
+
+mod error;
+pub use crate::error::Error;
+
+/// Commercial regions Lambda is available in without opting in, scanned by `--all-regions`
+pub const LAMBDA_REGIONS: &[Region] = &[
+    Region::UsEast1,
+    Region::UsEast2,
+    Region::UsWest1,
+    Region::UsWest2,
+    Region::CaCentral1,
+    Region::SaEast1,
+    Region::EuCentral1,
+    Region::EuWest1,
+    Region::EuWest2,
+    Region::EuWest3,
+    Region::EuNorth1,
+    Region::ApNortheast1,
+    Region::ApNortheast2,
+    Region::ApSouth1,
+    Region::ApSoutheast1,
+    Region::ApSoutheast2,
+];
+
+/// Runtimes AWS has retired or scheduled for retirement
+///
+/// See https://docs.aws.amazon.com/lambda/latest/dg/lambda-runtimes.html#runtimes-deprecated
+const DEPRECATED_RUNTIMES: &[&str] = &[
+    "dotnet5.0",
+    "dotnet6",
+    "dotnetcore1.0",
+    "dotnetcore2.0",
+    "dotnetcore2.1",
+    "dotnetcore3.1",
+    "go1.x",
+    "java8",
+    "nodejs",
+    "nodejs4.3",
+    "nodejs4.3-edge",
+    "nodejs6.10",
+    "nodejs8.10",
+    "nodejs10.x",
+    "nodejs12.x",
+    "nodejs14.x",
+    "nodejs16.x",
+    "nodejs18.x",
+    "provided",
+    "python2.7",
+    "python3.6",
+    "python3.7",
+    "python3.8",
+    "python3.9",
+    "ruby2.5",
+    "ruby2.7",
+    "ruby3.2",
+];
+
+/// Code size, in bytes, above which sizes are highlighted in colored output
+const LARGE_CODE_SIZE: i64 = 25 * 1024 * 1024;
+
+/// Bytes in a GB as AWS bills and limits storage, i.e. a GiB
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Default quota on the total code size of an account's functions and versions, in GB per region
+pub const CODE_STORAGE_QUOTA_GB: f64 = 75.0;
+
+/// Share of the code storage quota above which a warning is logged
+const CODE_STORAGE_WARNING: f64 = 0.8;
+
+/// Regions where Lambda and the tagging API publish FIPS endpoints
+pub const FIPS_REGIONS: &[Region] = &[
+    Region::UsEast1,
+    Region::UsEast2,
+    Region::UsWest1,
+    Region::UsWest2,
+    Region::UsGovEast1,
+    Region::UsGovWest1,
+];
+
+/// Endpoint of a custom region marking that clients should use each service's FIPS endpoint
+const FIPS_MARKER: &str = "fips";
+
+/// Largest zipped deployment package Lambda accepts when uploaded directly, rather than from S3
+pub const DIRECT_UPLOAD_LIMIT: i64 = 50 * 1024 * 1024;
+
+/// Default percentage of the direct upload limit within which a function is near it
+pub const NEAR_LIMIT_PCT: f64 = 10.0;
+
+/// Timeout in seconds above which a function with high memory too is a cost outlier
+pub const OUTLIER_TIMEOUT_SECS: i64 = 60;
+
+/// Memory in MB above which a function with a high timeout too is a cost outlier
+pub const OUTLIER_MEMORY_MB: i64 = 1024;
+
+/// Remaining lifetime in seconds below which expiring credentials may not outlast a long run
+pub const MIN_CREDENTIAL_TTL_SECS: i64 = 15 * 60;
+
+/// Rough US dollar price of storing a GB of code for a month, which varies by region
+pub const STORAGE_PRICE_PER_GB: f64 = 0.10;
+
+/// Number of published versions above which a function is highlighted as worth pruning
+const MANY_VERSIONS: usize = 50;
+
+/// Number of pages fetched from a paginated API before giving up on it ever finishing
+const MAX_PAGES: usize = 1000;
+
+/// Most tagged functions looked up one at a time rather than listing every function, about the
+/// point where individual lookups stop being cheaper than listing a typical account
+const MAX_LOOKUPS: usize = 50;
+
+/// Number of individual function lookups in flight at once
+const LOOKUP_PARALLELISM: usize = 8;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Sort {
+    Name,
+    Runtime,
+    CodeSize,
+    Memory,
+    LastModified,
+    TagCount,
+}
+
+impl Sort {
+    /// Names accepted for each sort key, where `size` is an alias of `codesize`
+    pub fn variants() -> &'static [&'static str] {
+        &[
+            "name",
+            "runtime",
+            "codesize",
+            "size",
+            "memory",
+            "lastmodified",
+            "tagcount",
+        ]
+    }
+}
+
+impl FromStr for Sort {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Sort::Name),
+            "runtime" => Ok(Sort::Runtime),
+            "codesize" | "size" => Ok(Sort::CodeSize),
+            "memory" => Ok(Sort::Memory),
+            "lastmodified" => Ok(Sort::LastModified),
+            "tagcount" => Ok(Sort::TagCount),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Sort::Name => "name",
+                Sort::Runtime => "runtime",
+                Sort::CodeSize => "codesize",
+                Sort::Memory => "memory",
+                Sort::LastModified => "lastmodified",
+                Sort::TagCount => "tagcount",
+            }
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Output {
+    Text,
+    Csv,
+    Tsv,
+    Yaml,
+    Markdown,
+    Prometheus,
+    Table,
+    Jsonl,
+}
+
+impl Output {
+    pub fn variants() -> &'static [&'static str] {
+        &[
+            "text",
+            "csv",
+            "tsv",
+            "yaml",
+            "markdown",
+            "prometheus",
+            "table",
+            "jsonl",
+        ]
+    }
+
+    /// Infer a format from a file name's extension, e.g. `csv` for `report.csv`
+    ///
+    /// `.json` isn't inferred, since newline delimited records aren't a JSON document
+    pub fn from_extension(path: &Path) -> Option<Output> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "txt" => Some(Output::Text),
+            "csv" => Some(Output::Csv),
+            "tsv" => Some(Output::Tsv),
+            "yaml" | "yml" => Some(Output::Yaml),
+            "md" => Some(Output::Markdown),
+            "prom" => Some(Output::Prometheus),
+            "jsonl" => Some(Output::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Output {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Output::Text),
+            "csv" => Ok(Output::Csv),
+            "tsv" => Ok(Output::Tsv),
+            "yaml" => Ok(Output::Yaml),
+            "markdown" => Ok(Output::Markdown),
+            "prometheus" => Ok(Output::Prometheus),
+            "table" => Ok(Output::Table),
+            "jsonl" => Ok(Output::Jsonl),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Output::Text => "text",
+                Output::Csv => "csv",
+                Output::Tsv => "tsv",
+                Output::Yaml => "yaml",
+                Output::Markdown => "markdown",
+                Output::Prometheus => "prometheus",
+                Output::Table => "table",
+                Output::Jsonl => "jsonl",
+            }
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Column {
+    Name,
+    Runtime,
+    Size,
+    Memory,
+    Timeout,
+    Tags,
+    Dlq,
+    Handler,
+    Arn,
+    Concurrency,
+    Sha256,
+    Region,
+    Tracing,
+    TagCount,
+    Description,
+    LogGroup,
+    Age,
+}
+
+impl Column {
+    pub fn variants() -> &'static [&'static str] {
+        &[
+            "name",
+            "runtime",
+            "size",
+            "memory",
+            "timeout",
+            "tags",
+            "dlq",
+            "handler",
+            "arn",
+            "concurrency",
+            "sha256",
+            "region",
+            "tracing",
+            "tagcount",
+            "description",
+            "loggroup",
+            "age",
+        ]
+    }
+
+    /// Columns rendered when none are selected
+    pub fn defaults() -> Vec<Column> {
+        vec![
+            Column::Name,
+            Column::Runtime,
+            Column::Size,
+            Column::Memory,
+            Column::Timeout,
+        ]
+    }
+
+    /// Return the value of this column for a given function for human display
+    pub fn value(
+        &self,
+        func: &Func,
+        units: Units,
+    ) -> String {
+        match self {
+            Column::Name => func.name().unwrap_or_else(|| "-".into()),
+            Column::Runtime => func.runtime().unwrap_or_else(|| "-".into()),
+            Column::Size => func.human_size(units),
+            Column::Memory => func.human_memory(),
+            Column::Timeout => func.human_timeout(),
+            Column::Tags => func.human_tags(),
+            Column::Dlq => func.dead_letter_target().unwrap_or_else(|| "none".into()),
+            Column::Handler => func.handler().unwrap_or_else(|| "-".into()),
+            Column::Arn => func.arn().unwrap_or_else(|| "-".into()),
+            Column::Concurrency => func.human_concurrency(),
+            Column::Sha256 => func.code_sha256().unwrap_or_else(|| "-".into()),
+            Column::Tracing => func.tracing().to_string(),
+            Column::TagCount => func.tags.len().to_string(),
+            Column::Description => func.description().unwrap_or_else(|| "-".into()),
+            Column::LogGroup => func.log_group().unwrap_or_else(|| "-".into()),
+            Column::Age => func.human_age(Utc::now()),
+            Column::Region => match func.region() {
+                Some(region) => region.name().into(),
+                None => "-".into(),
+            },
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Column::Name),
+            "runtime" => Ok(Column::Runtime),
+            "size" => Ok(Column::Size),
+            "memory" => Ok(Column::Memory),
+            "timeout" => Ok(Column::Timeout),
+            "tags" => Ok(Column::Tags),
+            "dlq" => Ok(Column::Dlq),
+            "handler" => Ok(Column::Handler),
+            "arn" => Ok(Column::Arn),
+            "concurrency" => Ok(Column::Concurrency),
+            "sha256" => Ok(Column::Sha256),
+            "region" => Ok(Column::Region),
+            "tracing" => Ok(Column::Tracing),
+            "tagcount" => Ok(Column::TagCount),
+            "description" => Ok(Column::Description),
+            "loggroup" => Ok(Column::LogGroup),
+            "age" => Ok(Column::Age),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Column::Name => "name",
+                Column::Runtime => "runtime",
+                Column::Size => "size",
+                Column::Memory => "memory",
+                Column::Timeout => "timeout",
+                Column::Tags => "tags",
+                Column::Dlq => "dlq",
+                Column::Handler => "handler",
+                Column::Arn => "arn",
+                Column::Concurrency => "concurrency",
+                Column::Sha256 => "sha256",
+                Column::Region => "region",
+                Column::Tracing => "tracing",
+                Column::TagCount => "tagcount",
+                Column::Description => "description",
+                Column::LogGroup => "loggroup",
+                Column::Age => "age",
+            }
+        )
+    }
+}
+
+/// How failures are printed to stderr
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorFormat {
+    Text,
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["text", "json"]
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for ErrorFormat {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorFormat::Text => "text",
+                ErrorFormat::Json => "json",
+            }
+        )
+    }
+}
+
+/// How multiple tag filters combine
+///
+/// `all` filters with the tagging API, which requires every key to match. `any` lists every
+/// function and keeps those carrying at least one of the tags
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TagFilterMode {
+    All,
+    Any,
+}
+
+impl TagFilterMode {
+    pub fn variants() -> &'static [&'static str] {
+        &["all", "any"]
+    }
+}
+
+impl FromStr for TagFilterMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(TagFilterMode::All),
+            "any" => Ok(TagFilterMode::Any),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for TagFilterMode {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TagFilterMode::All => "all",
+                TagFilterMode::Any => "any",
+            }
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Units {
+    Conventional,
+    Binary,
+    Decimal,
+}
+
+impl Units {
+    pub fn variants() -> &'static [&'static str] {
+        &["conventional", "binary", "decimal"]
+    }
+
+    /// Return a byte size formatted for human display in these units
+    pub fn format(
+        &self,
+        size: i64,
+    ) -> String {
+        size.file_size(match self {
+            Units::Conventional => options::CONVENTIONAL,
+            Units::Binary => options::BINARY,
+            Units::Decimal => options::DECIMAL,
+        })
+        .unwrap_or_default()
+    }
+}
+
+impl FromStr for Units {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "conventional" => Ok(Units::Conventional),
+            "binary" => Ok(Units::Binary),
+            "decimal" => Ok(Units::Decimal),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Units::Conventional => "conventional",
+                Units::Binary => "binary",
+                Units::Decimal => "decimal",
+            }
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GroupBy {
+    Runtime,
+}
+
+impl GroupBy {
+    pub fn variants() -> &'static [&'static str] {
+        &["runtime"]
+    }
+
+    /// Return the key a function is grouped under
+    pub fn key(
+        &self,
+        func: &Func,
+    ) -> Option<String> {
+        match self {
+            GroupBy::Runtime => func.runtime(),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "runtime" => Ok(GroupBy::Runtime),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                GroupBy::Runtime => "runtime",
+            }
+        )
+    }
+}
+
+/// X-Ray tracing mode of a function
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Tracing {
+    PassThrough,
+    Active,
+}
+
+impl Tracing {
+    pub fn variants() -> &'static [&'static str] {
+        &["passthrough", "active"]
+    }
+}
+
+impl FromStr for Tracing {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "passthrough" => Ok(Tracing::PassThrough),
+            "active" => Ok(Tracing::Active),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for Tracing {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Tracing::PassThrough => "passthrough",
+                Tracing::Active => "active",
+            }
+        )
+    }
+}
+
+/// Language a runtime belongs to, regardless of its version
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RuntimeFamily {
+    Node,
+    Python,
+    Java,
+    Go,
+    Dotnet,
+    Ruby,
+}
+
+impl RuntimeFamily {
+    pub fn variants() -> &'static [&'static str] {
+        &["node", "python", "java", "go", "dotnet", "ruby"]
+    }
+
+    /// Prefix shared by the runtime identifiers of this family
+    fn prefix(self) -> &'static str {
+        match self {
+            RuntimeFamily::Node => "nodejs",
+            RuntimeFamily::Python => "python",
+            RuntimeFamily::Java => "java",
+            RuntimeFamily::Go => "go",
+            RuntimeFamily::Dotnet => "dotnet",
+            RuntimeFamily::Ruby => "ruby",
+        }
+    }
+}
+
+impl FromStr for RuntimeFamily {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "node" => Ok(RuntimeFamily::Node),
+            "python" => Ok(RuntimeFamily::Python),
+            "java" => Ok(RuntimeFamily::Java),
+            "go" => Ok(RuntimeFamily::Go),
+            "dotnet" => Ok(RuntimeFamily::Dotnet),
+            "ruby" => Ok(RuntimeFamily::Ruby),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeFamily {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RuntimeFamily::Node => "node",
+                RuntimeFamily::Python => "python",
+                RuntimeFamily::Java => "java",
+                RuntimeFamily::Go => "go",
+                RuntimeFamily::Dotnet => "dotnet",
+                RuntimeFamily::Ruby => "ruby",
+            }
+        )
+    }
+}
+
+/// A single lambda function with associated tags
+#[derive(Debug, Default)]
+pub struct Func {
+    pub config: FunctionConfiguration,
+    pub tags: Vec<Tag>,
+    /// Reserved concurrent executions, only fetched on request
+    pub reserved_concurrency: Option<i64>,
+}
+
+impl From<GetFunctionResponse> for Func {
+    fn from(response: GetFunctionResponse) -> Self {
+        let mut tags = response
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| Tag { key, value })
+            .collect::<Vec<_>>();
+        tags.sort_by(|a, b| a.key.cmp(&b.key));
+        Func {
+            config: response.configuration.unwrap_or_default(),
+            tags,
+            reserved_concurrency: response
+                .concurrency
+                .and_then(|concurrency| concurrency.reserved_concurrent_executions),
+        }
+    }
+}
+
+impl Func {
+    /// Return size of function for human display
+    pub fn human_size(
+        &self,
+        units: Units,
+    ) -> String {
+        units.format(self.code_size().unwrap_or_default())
+    }
+
+    /// Return configured memory of function for human display
+    pub fn human_memory(&self) -> String {
+        self.memory_size()
+            .map(|size| format!("{} MB", size))
+            .unwrap_or_else(|| "-".into())
+    }
+
+    /// Return tags of function as sorted `key=value` pairs for human display
+    pub fn human_tags(&self) -> String {
+        if self.tags.is_empty() {
+            return "-".into();
+        }
+        let mut tags = self
+            .tags
+            .iter()
+            .map(|tag| format!("{}={}", tag.key, tag.value))
+            .collect::<Vec<_>>();
+        tags.sort_unstable();
+        tags.join(",")
+    }
+
+    /// Return reserved concurrency of function for human display
+    pub fn human_concurrency(&self) -> String {
+        match self.reserved_concurrency {
+            Some(reserved) => reserved.to_string(),
+            None => "unreserved".into(),
+        }
+    }
+
+    /// Return configured timeout of function for human display
+    pub fn human_timeout(&self) -> String {
+        self.timeout()
+            .map(|secs| format!("{}s", secs))
+            .unwrap_or_else(|| "-".into())
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.config.function_name.clone()
+    }
+
+    pub fn arn(&self) -> Option<String> {
+        self.config.function_arn.clone()
+    }
+
+    /// Return the region a function belongs to, parsed from its ARN
+    pub fn region(&self) -> Option<Region> {
+        self.config
+            .function_arn
+            .as_ref()
+            .and_then(|arn| arn.split(':').nth(3))
+            .and_then(|region| region.parse().ok())
+    }
+
+    pub fn runtime(&self) -> Option<String> {
+        self.config.runtime.clone()
+    }
+
+    pub fn code_size(&self) -> Option<i64> {
+        self.config.code_size
+    }
+
+    pub fn memory_size(&self) -> Option<i64> {
+        self.config.memory_size
+    }
+
+    pub fn handler(&self) -> Option<String> {
+        self.config.handler.clone()
+    }
+
+    /// Return the language family of the runtime, e.g. python for python3.7
+    pub fn runtime_family(&self) -> Option<RuntimeFamily> {
+        let runtime = self.config.runtime.as_ref()?;
+        [
+            RuntimeFamily::Node,
+            RuntimeFamily::Python,
+            RuntimeFamily::Java,
+            RuntimeFamily::Go,
+            RuntimeFamily::Dotnet,
+            RuntimeFamily::Ruby,
+        ]
+        .iter()
+        .cloned()
+        .find(|family| runtime.starts_with(family.prefix()))
+    }
+
+    /// Return the tracing mode, which defaults to pass through when not configured
+    pub fn tracing(&self) -> Tracing {
+        self.config
+            .tracing_config
+            .as_ref()
+            .and_then(|config| config.mode.as_ref())
+            .and_then(|mode| mode.parse().ok())
+            .unwrap_or(Tracing::PassThrough)
+    }
+
+    pub fn code_sha256(&self) -> Option<String> {
+        self.config.code_sha_256.clone()
+    }
+
+    /// Return the CloudWatch log group Lambda writes the function's logs to by default
+    pub fn log_group(&self) -> Option<String> {
+        self.name().map(|name| format!("/aws/lambda/{}", name))
+    }
+
+    /// Return the description of the function, which the API reports as empty when unset
+    pub fn description(&self) -> Option<String> {
+        self.config
+            .description
+            .clone()
+            .filter(|description| !description.is_empty())
+    }
+
+    pub fn dead_letter_target(&self) -> Option<String> {
+        self.config
+            .dead_letter_config
+            .as_ref()
+            .and_then(|config| config.target_arn.clone())
+    }
+
+    /// Return the VPC configuration of a VPC attached function
+    ///
+    /// Functions outside a VPC may still report a configuration with an empty VPC id
+    pub fn vpc(&self) -> Option<&VpcConfigResponse> {
+        self.config
+            .vpc_config
+            .as_ref()
+            .filter(|vpc| match &vpc.vpc_id {
+                Some(id) => !id.is_empty(),
+                None => false,
+            })
+    }
+
+    pub fn timeout(&self) -> Option<i64> {
+        self.config.timeout
+    }
+
+    /// Return the versioned ARNs of layers attached to the function
+    pub fn layers(&self) -> Vec<String> {
+        self.config
+            .layers
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|layer| layer.arn)
+            .collect()
+    }
+
+    /// Return environment variables of function sorted by name
+    pub fn env_vars(&self) -> BTreeMap<String, String> {
+        self.config
+            .environment
+            .clone()
+            .and_then(|env| env.variables)
+            .unwrap_or_default()
+            .into_iter()
+            .collect()
+    }
+
+    /// Return true if the function defines any environment variables
+    pub fn has_env_vars(&self) -> bool {
+        match self
+            .config
+            .environment
+            .as_ref()
+            .and_then(|env| env.variables.as_ref())
+        {
+            Some(variables) => !variables.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Return true if the function runs on a runtime AWS has deprecated
+    pub fn is_deprecated_runtime(&self) -> bool {
+        match &self.config.runtime {
+            Some(runtime) => DEPRECATED_RUNTIMES.contains(&runtime.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Return where to move a deprecated runtime whose migration is more than a version bump
+    ///
+    /// Go1.x functions move to an OS only runtime, rebuilt as an executable named `bootstrap`
+    pub fn runtime_suggestion(&self) -> Option<&'static str> {
+        match self.config.runtime.as_deref() {
+            Some("go1.x") => {
+                Some("provided.al2023 or provided.al2, built as a bootstrap executable")
+            }
+            _ => None,
+        }
+    }
+
+    /// Return true if no pattern is provided or the function name matches it
+    pub fn matches_name(
+        &self,
+        pattern: Option<&Regex>,
+    ) -> bool {
+        match (pattern, self.name()) {
+            (None, _) => true,
+            (Some(pattern), Some(name)) => pattern.is_match(&name),
+            _ => false,
+        }
+    }
+
+    /// Return true if no ARNs are provided or the function's ARN is one of them
+    pub fn in_arns(
+        &self,
+        arns: Option<&BTreeSet<String>>,
+    ) -> bool {
+        match arns {
+            Some(arns) => self.arn().iter().any(|arn| arns.contains(arn)),
+            None => true,
+        }
+    }
+
+    /// Return true if no hash is provided or the function's code has the provided SHA256 hash
+    pub fn matches_sha256(
+        &self,
+        sha256: Option<&str>,
+    ) -> bool {
+        match sha256 {
+            Some(sha256) => self.config.code_sha_256.as_deref() == Some(sha256),
+            None => true,
+        }
+    }
+
+    /// Return true if no text is provided or the description contains it, ignoring case
+    pub fn matches_description(
+        &self,
+        text: Option<&str>,
+    ) -> bool {
+        match (text, self.description()) {
+            (None, _) => true,
+            (Some(text), Some(description)) => {
+                description.to_lowercase().contains(&text.to_lowercase())
+            }
+            _ => false,
+        }
+    }
+
+    pub fn at_least_timeout(
+        &self,
+        min_timeout: Option<i64>,
+    ) -> bool {
+        match (min_timeout, self.timeout()) {
+            (None, _) => true,
+            (Some(min_timeout), Some(timeout)) => timeout >= min_timeout,
+            _ => false,
+        }
+    }
+
+    /// Return true if no minimum is provided or the code size is known and at least the minimum
+    pub fn at_least_size(
+        &self,
+        min_size: Option<i64>,
+    ) -> bool {
+        match (min_size, self.code_size()) {
+            (None, _) => true,
+            (Some(min_size), Some(code_size)) => code_size >= min_size,
+            _ => false,
+        }
+    }
+
+    /// Return why a function is a cost outlier when both its timeout and memory exceed the
+    /// given limits, since a stuck invocation then bills the most
+    pub fn outlier_reason(
+        &self,
+        timeout_secs: i64,
+        memory_mb: i64,
+    ) -> Option<String> {
+        match (self.timeout(), self.memory_size()) {
+            (Some(timeout), Some(memory)) if timeout > timeout_secs && memory > memory_mb => {
+                Some(format!(
+                    "timeout {}s over {}s and memory {} MB over {} MB",
+                    timeout, timeout_secs, memory, memory_mb
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the bytes left under the direct upload limit when the code size is within `pct`
+    /// percent of it, negative once it is over
+    pub fn upload_headroom(
+        &self,
+        pct: f64,
+    ) -> Option<i64> {
+        let code_size = self.code_size()?;
+        let threshold = DIRECT_UPLOAD_LIMIT as f64 * (1.0 - pct / 100.0);
+        if code_size as f64 >= threshold {
+            Some(DIRECT_UPLOAD_LIMIT - code_size)
+        } else {
+            None
+        }
+    }
+
+    /// Return true if no maximum is provided or the code size is known and at most the maximum
+    pub fn at_most_size(
+        &self,
+        max_size: Option<i64>,
+    ) -> bool {
+        match (max_size, self.code_size()) {
+            (None, _) => true,
+            (Some(max_size), Some(code_size)) => code_size <= max_size,
+            _ => false,
+        }
+    }
+
+    /// Return true if the function is tagged with the given key and value
+    pub fn has_tag(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> bool {
+        self.tags
+            .iter()
+            .any(|tag| tag.key == key && tag.value == value)
+    }
+
+    /// Return true if the function is tagged with the given key, whatever its value
+    pub fn has_tag_key(
+        &self,
+        key: &str,
+    ) -> bool {
+        self.tags.iter().any(|tag| tag.key == key)
+    }
+
+    /// Return the audit problems of this function, e.g. a deprecated runtime, in a fixed order
+    pub fn problems(&self) -> Vec<&'static str> {
+        let mut problems = Vec::new();
+        if self.is_deprecated_runtime() {
+            problems.push("deprecated runtime");
+        }
+        if self.tags.is_empty() {
+            problems.push("untagged");
+        }
+        if self.dead_letter_target().is_none() {
+            problems.push("no dlq");
+        }
+        if self.tracing() != Tracing::Active {
+            problems.push("no tracing");
+        }
+        problems
+    }
+
+    /// Return the value of the given tag key, if the function carries it
+    pub fn tag_value(
+        &self,
+        key: &str,
+    ) -> Option<String> {
+        self.tags
+            .iter()
+            .find(|tag| tag.key == key)
+            .map(|tag| tag.value.clone())
+    }
+
+    /// Return the required tag keys this function does not carry, in the order required
+    pub fn missing_tags<'a>(
+        &self,
+        required: &'a [String],
+    ) -> Vec<&'a str> {
+        required
+            .iter()
+            .filter(|key| !self.tags.iter().any(|tag| &tag.key == *key))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Return true if the function was last modified on or after `after` and before `before`
+    ///
+    /// Dates are compared in UTC. Functions without a parseable timestamp only match when
+    /// neither bound is provided
+    pub fn modified_between(
+        &self,
+        after: Option<NaiveDate>,
+        before: Option<NaiveDate>,
+    ) -> bool {
+        if after.is_none() && before.is_none() {
+            return true;
+        }
+        match self.last_modified() {
+            Some(modified) => {
+                let date = modified.naive_utc().date();
+                after.into_iter().all(|after| date >= after)
+                    && before.into_iter().all(|before| date < before)
+            }
+            None => false,
+        }
+    }
+
+    /// Return the time of the last function update, if it could be parsed
+    pub fn last_modified(&self) -> Option<DateTime<FixedOffset>> {
+        self.config
+            .last_modified
+            .as_ref()
+            .and_then(|s| DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z").ok())
+    }
+
+    /// Return the time since the last function update as of `now` for human display,
+    /// e.g. `3d ago`, in the largest whole unit
+    pub fn human_age(
+        &self,
+        now: DateTime<Utc>,
+    ) -> String {
+        match self.last_modified() {
+            Some(modified) => {
+                let seconds = cmp::max(0, (now - modified.with_timezone(&Utc)).num_seconds());
+                let (count, unit) = [
+                    (365 * 24 * 60 * 60, "y"),
+                    (30 * 24 * 60 * 60, "mo"),
+                    (24 * 60 * 60, "d"),
+                    (60 * 60, "h"),
+                    (60, "m"),
+                ]
+                .iter()
+                .find(|(length, _)| seconds >= *length)
+                .map(|(length, unit)| (seconds / length, *unit))
+                .unwrap_or((seconds, "s"));
+                format!("{}{} ago", count, unit)
+            }
+            None => "-".into(),
+        }
+    }
+}
+
+/// Serializable view of a function for structured output formats
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record {
+    pub name: Option<String>,
+    pub arn: Option<String>,
+    pub runtime: Option<String>,
+    pub code_size: Option<i64>,
+    pub code_sha256: Option<String>,
+    pub human_size: String,
+    pub memory_size: Option<i64>,
+    pub timeout: Option<i64>,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Record {
+    pub fn new(
+        func: &Func,
+        units: Units,
+    ) -> Self {
+        Record {
+            name: func.name(),
+            arn: func.arn(),
+            runtime: func.runtime(),
+            code_size: func.code_size(),
+            code_sha256: func.code_sha256(),
+            human_size: func.human_size(units),
+            memory_size: func.memory_size(),
+            timeout: func.timeout(),
+            tags: func
+                .tags
+                .iter()
+                .map(|tag| (tag.key.clone(), tag.value.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Split comma separated tag values into one pair per value, so `team=a,b` filters on
+/// either team. A value containing a comma escapes it as `\\,`
+pub fn split_tag_values(tags: Vec<(String, String)>) -> Vec<(String, String)> {
+    tags.into_iter()
+        .flat_map(|(k, v)| {
+            let mut values = vec![String::new()];
+            let mut chars = v.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' if chars.as_str().starts_with(',') => {
+                        chars.next();
+                        values.last_mut().expect("at least one value").push(',')
+                    }
+                    ',' => values.push(String::new()),
+                    c => values.last_mut().expect("at least one value").push(c),
+                }
+            }
+            values.into_iter().map(move |v| (k.clone(), v))
+        })
+        .collect()
+}
+
+/// Convert tag key value pairs into tagging API filters
+///
+/// Values for a repeated key, or listed together as `key=a,b`, are coalesced into one
+/// filter, which the API matches if any of them match, while filters for different keys
+/// must all match
+pub fn filters(tags: Vec<(String, String)>) -> Vec<TagFilter> {
+    split_tag_values(tags)
+        .into_iter()
+        .fold(Vec::<TagFilter>::new(), |mut filters, (k, v)| {
+            match filters
+                .iter_mut()
+                .find(|filter| filter.key.as_ref() == Some(&k))
+            {
+                Some(filter) => filter.values.get_or_insert_with(Vec::new).push(v),
+                None => filters.push(TagFilter {
+                    key: Some(k),
+                    values: Some(vec![v]),
+                }),
+            }
+            filters
+        })
+}
+
+/// Stream pages from a paginated API as they are fetched
+///
+/// `fetch` is called with the token returned by the page before it, starting with none, until
+/// a page comes back without a next token. Each request depends on the one before it, so pages
+/// are requested one after another but are handed downstream as soon as they arrive. Fails once
+/// `max_pages` pages have been fetched and the API still returns a next token, guarding
+/// against tokens that never advance
+fn paginate<T, F, R>(
+    api: &'static str,
+    max_pages: usize,
+    mut fetch: F,
+) -> impl Stream<Item = Vec<T>, Error = Error> + Send
+where
+    F: FnMut(Option<String>) -> R + Send,
+    R: Future<Item = (Vec<T>, Option<String>), Error = Error> + Send,
+    T: Send,
+{
+    stream::unfold(
+        (Some(None), 0),
+        move |(token, pages): (Option<Option<String>>, usize)| {
+            token.map(|token| {
+                if pages >= max_pages {
+                    return future::Either::A(future::err(Error::Pagination(api, max_pages)));
+                }
+                future::Either::B(fetch(token).map(move |(items, next)| {
+                    let next = next.filter(|s| !s.is_empty()).map(Some);
+                    (items, (next, pages + 1))
+                }))
+            })
+        },
+    )
+}
+
+/// Stream pages of lambda configurations as they are fetched
+pub fn lambda_pages(
+    client: LambdaClient,
+    page_size: i64,
+    retries: Retries,
+) -> impl Stream<Item = Vec<FunctionConfiguration>, Error = Error> + Send {
+    paginate("lambda", MAX_PAGES, move |marker| {
+        let client = client.clone();
+        let limiter = retries.limiter.clone();
+        backoff(&retries)
+            .retry_if(
+                move || {
+                    limiter.limit(client.list_functions(ListFunctionsRequest {
+                        max_items: Some(page_size),
+                        marker: marker.clone(),
+                        ..ListFunctionsRequest::default()
+                    }))
+                },
+                |err: &RusotoError<ListFunctionsError>| {
+                    log::debug!("lambda api error {}", err);
+                    lambdas_retryable(err)
+                },
+            )
+            .map(|result| (result.functions.unwrap_or_default(), result.next_marker))
+            .map_err(Error::from)
+    })
+}
+
+pub fn lambdas(
+    client: LambdaClient,
+    page_size: i64,
+    retries: Retries,
+    progress: Progress,
+) -> Box<dyn Future<Item = Vec<FunctionConfiguration>, Error = Error> + Send> {
+    Box::new(unique_lambdas(
+        lambda_pages(client, page_size, retries).inspect(move |page| progress.lambdas(page.len())),
+    ))
+}
+
+/// Collect pages of lambda configurations, keeping only the first of any repeated ARN
+///
+/// The API can return a function on more than one page while functions are being deployed
+fn unique_lambdas<S>(pages: S) -> impl Future<Item = Vec<FunctionConfiguration>, Error = S::Error>
+where
+    S: Stream<Item = Vec<FunctionConfiguration>>,
+{
+    pages
+        .fold(
+            (HashSet::new(), Vec::new()),
+            |(mut seen, mut lambdas), page| {
+                for config in page {
+                    if seen.insert(config.function_arn.clone()) {
+                        lambdas.push(config)
+                    }
+                }
+                future::ok((seen, lambdas))
+            },
+        )
+        .map(|(_, lambdas)| lambdas)
+}
+
+pub fn function(
+    client: LambdaClient,
+    name: String,
+    retries: Retries,
+) -> Box<dyn Future<Item = GetFunctionResponse, Error = RusotoError<GetFunctionError>> + Send> {
+    let limiter = retries.limiter.clone();
+    Box::new(backoff(&retries).retry_if(
+        move || {
+            limiter.limit(client.get_function(GetFunctionRequest {
+                function_name: name.clone(),
+                ..GetFunctionRequest::default()
+            }))
+        },
+        |err: &RusotoError<GetFunctionError>| {
+            log::debug!("lambda api error {}", err);
+            match err {
+                RusotoError::Service(GetFunctionError::TooManyRequests(_)) => true,
+                _ => false,
+            }
+        },
+    ))
+}
+
+/// Fetch the configuration of each function by ARN, skipping functions that no longer exist,
+/// which the tagging API may still return for a while after they are deleted
+pub fn lambdas_by_arn(
+    client: LambdaClient,
+    arns: Vec<String>,
+    retries: Retries,
+    progress: Progress,
+) -> Box<dyn Future<Item = Vec<FunctionConfiguration>, Error = Error> + Send> {
+    Box::new(
+        stream::iter_ok(arns)
+            .map(move |arn| {
+                function(client.clone(), arn, retries.clone()).then(|result| match result {
+                    Ok(response) => future::ok::<_, Error>(response.configuration),
+                    Err(RusotoError::Service(GetFunctionError::ResourceNotFound(_))) => {
+                        future::ok(None)
+                    }
+                    Err(err) => future::err(Error::from(err)),
+                })
+            })
+            .buffered(LOOKUP_PARALLELISM)
+            .filter_map(|config| config)
+            .inspect(move |_| progress.lambdas(1))
+            .collect(),
+    )
+}
+
+/// Map each function through `f`, with at most `limit` of the returned futures in flight
+///
+/// Results are collected in the order of `funcs`, so `f` can return the function alongside
+/// what it fetched to keep them associated. A `limit` of 0 runs one at a time
+pub fn for_each_func_concurrent<F, Fut>(
+    funcs: Vec<Func>,
+    limit: usize,
+    f: F,
+) -> Box<dyn Future<Item = Vec<Fut::Item>, Error = Fut::Error> + Send>
+where
+    F: FnMut(Func) -> Fut + Send + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Item: Send + 'static,
+    Fut::Error: Send + 'static,
+{
+    Box::new(
+        stream::iter_ok(funcs)
+            .map(f)
+            .buffered(limit.max(1))
+            .collect(),
+    )
+}
+
+/// Fetch the reserved concurrency of each function, with at most `parallelism` requests in flight
+///
+/// Each function is looked up in the region of its ARN, falling back to the provided region
+pub fn with_concurrency(
+    credentials: Credentials,
+    region: Region,
+    funcs: Vec<Func>,
+    retries: Retries,
+    parallelism: usize,
+) -> Box<dyn Future<Item = Vec<Func>, Error = Error> + Send> {
+    let clients = match regional_clients(credentials, &region, &funcs) {
+        Ok(clients) => clients,
+        Err(name) => return Box::new(future::err(Error::Fips(name))),
+    };
+    for_each_func_concurrent(funcs, parallelism, move |mut func| {
+        let client = clients[func.region().unwrap_or_else(|| region.clone()).name()].clone();
+        function(client, func.name().unwrap_or_default(), retries.clone())
+            .map(move |response| {
+                func.reserved_concurrency = response
+                    .concurrency
+                    .and_then(|concurrency| concurrency.reserved_concurrent_executions);
+                func
+            })
+            .map_err(Error::from)
+    })
+}
+
+/// Stream pages of a function's aliases as they are fetched
+pub fn alias_pages(
+    client: LambdaClient,
+    name: String,
+    retries: Retries,
+) -> impl Stream<Item = Vec<AliasConfiguration>, Error = Error> + Send {
+    paginate("alias", MAX_PAGES, move |marker| {
+        let client = client.clone();
+        let name = name.clone();
+        let limiter = retries.limiter.clone();
+        backoff(&retries)
+            .retry_if(
+                move || {
+                    limiter.limit(client.list_aliases(ListAliasesRequest {
+                        function_name: name.clone(),
+                        marker: marker.clone(),
+                        ..ListAliasesRequest::default()
+                    }))
+                },
+                |err: &RusotoError<ListAliasesError>| {
+                    log::debug!("lambda api error {}", err);
+                    match err {
+                        RusotoError::Service(ListAliasesError::TooManyRequests(_)) => true,
+                        _ => false,
+                    }
+                },
+            )
+            .map(|result| (result.aliases.unwrap_or_default(), result.next_marker))
+            .map_err(Error::from)
+    })
+}
+
+/// Fetch the aliases of each function, with at most `parallelism` functions looked up at once
+///
+/// Each function is looked up in the region of its ARN, falling back to the provided region
+pub fn with_aliases(
+    credentials: Credentials,
+    region: Region,
+    funcs: Vec<Func>,
+    retries: Retries,
+    parallelism: usize,
+) -> Box<dyn Future<Item = Vec<(Func, Vec<AliasConfiguration>)>, Error = Error> + Send> {
+    let clients = match regional_clients(credentials, &region, &funcs) {
+        Ok(clients) => clients,
+        Err(name) => return Box::new(future::err(Error::Fips(name))),
+    };
+    for_each_func_concurrent(funcs, parallelism, move |func| {
+        let client = clients[func.region().unwrap_or_else(|| region.clone()).name()].clone();
+        alias_pages(client, func.name().unwrap_or_default(), retries.clone())
+            .concat2()
+            .map(move |aliases| (func, aliases))
+    })
+}
+
+/// Stream pages of a function's versions, including `$LATEST`, as they are fetched
+pub fn version_pages(
+    client: LambdaClient,
+    name: String,
+    retries: Retries,
+) -> impl Stream<Item = Vec<FunctionConfiguration>, Error = Error> + Send {
+    paginate("version", MAX_PAGES, move |marker| {
+        let client = client.clone();
+        let name = name.clone();
+        let limiter = retries.limiter.clone();
+        backoff(&retries)
+            .retry_if(
+                move || {
+                    limiter.limit(
+                        client.list_versions_by_function(ListVersionsByFunctionRequest {
+                            function_name: name.clone(),
+                            marker: marker.clone(),
+                            ..ListVersionsByFunctionRequest::default()
+                        }),
+                    )
+                },
+                |err: &RusotoError<ListVersionsByFunctionError>| {
+                    log::debug!("lambda api error {}", err);
+                    match err {
+                        RusotoError::Service(ListVersionsByFunctionError::TooManyRequests(_)) => {
+                            true
+                        }
+                        _ => false,
+                    }
+                },
+            )
+            .map(|result| (result.versions.unwrap_or_default(), result.next_marker))
+            .map_err(Error::from)
+    })
+}
+
+/// Fetch the published versions of each function, with at most `parallelism` functions looked
+/// up at once
+///
+/// Each function is looked up in the region of its ARN, falling back to the provided region
+pub fn with_versions(
+    credentials: Credentials,
+    region: Region,
+    funcs: Vec<Func>,
+    retries: Retries,
+    parallelism: usize,
+) -> Box<dyn Future<Item = Vec<(Func, Versions)>, Error = Error> + Send> {
+    let clients = match regional_clients(credentials, &region, &funcs) {
+        Ok(clients) => clients,
+        Err(name) => return Box::new(future::err(Error::Fips(name))),
+    };
+    for_each_func_concurrent(funcs, parallelism, move |func| {
+        let client = clients[func.region().unwrap_or_else(|| region.clone()).name()].clone();
+        version_pages(client, func.name().unwrap_or_default(), retries.clone())
+            .concat2()
+            .map(move |versions| (func, Versions::from(versions.as_slice())))
+    })
+}
+
+/// Return a function's published versions that may be purged, newest first
+///
+/// Versions an alias points at, including through weighted routing, are never selected, and
+/// the newest `keep` of the remaining published versions are kept on top of them. `$LATEST`
+/// is not a published version and is never selected either
+pub fn purgeable_versions(
+    versions: &[FunctionConfiguration],
+    aliases: &[AliasConfiguration],
+    keep: usize,
+) -> Vec<FunctionConfiguration> {
+    let aliased = aliases
+        .iter()
+        .flat_map(|alias| {
+            let weighted = alias
+                .routing_config
+                .as_ref()
+                .and_then(|routing| routing.additional_version_weights.as_ref())
+                .map(|weights| weights.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            alias.function_version.clone().into_iter().chain(weighted)
+        })
+        .collect::<HashSet<_>>();
+    let mut published = versions
+        .iter()
+        .filter_map(|version| {
+            let number = version.version.as_ref()?.parse::<u64>().ok()?;
+            Some((number, version))
+        })
+        .collect::<Vec<_>>();
+    published.sort_by(|(a, _), (b, _)| b.cmp(a));
+    published
+        .into_iter()
+        .filter(|(_, version)| match &version.version {
+            Some(version) => !aliased.contains(version),
+            None => false,
+        })
+        .skip(keep)
+        .map(|(_, version)| version.clone())
+        .collect()
+}
+
+/// Fetch the versions of each function that may be purged, keeping the newest `keep`,
+/// with at most `parallelism` functions looked up at once
+///
+/// Each function is looked up in the region of its ARN, falling back to the provided region
+pub fn with_purgeable_versions(
+    credentials: Credentials,
+    region: Region,
+    funcs: Vec<Func>,
+    keep: usize,
+    retries: Retries,
+    parallelism: usize,
+) -> Box<dyn Future<Item = Vec<(Func, Vec<FunctionConfiguration>)>, Error = Error> + Send> {
+    let clients = match regional_clients(credentials, &region, &funcs) {
+        Ok(clients) => clients,
+        Err(name) => return Box::new(future::err(Error::Fips(name))),
+    };
+    for_each_func_concurrent(funcs, parallelism, move |func| {
+        let client = clients[func.region().unwrap_or_else(|| region.clone()).name()].clone();
+        let name = func.name().unwrap_or_default();
+        version_pages(client.clone(), name.clone(), retries.clone())
+            .concat2()
+            .join(alias_pages(client, name, retries.clone()).concat2())
+            .map(move |(versions, aliases)| {
+                let purgeable = purgeable_versions(&versions, &aliases, keep);
+                (func, purgeable)
+            })
+    })
+}
+
+/// Delete a single published version of a function
+///
+/// Fails without calling the API unless `version` is a version number, guarding against
+/// requests without a qualifier, which delete the function and all of its versions
+pub fn delete_version(
+    client: LambdaClient,
+    name: String,
+    version: String,
+    retries: Retries,
+) -> Box<dyn Future<Item = (), Error = Error> + Send> {
+    if name.is_empty() || version.parse::<u64>().is_err() {
+        return Box::new(future::err(Error::UnsafeDelete(name, version)));
+    }
+    let limiter = retries.limiter.clone();
+    Box::new(
+        backoff(&retries)
+            .retry_if(
+                move || {
+                    limiter.limit(client.delete_function(DeleteFunctionRequest {
+                        function_name: name.clone(),
+                        qualifier: Some(version.clone()),
+                    }))
+                },
+                |err: &RusotoError<DeleteFunctionError>| {
+                    log::debug!("lambda api error {}", err);
+                    match err {
+                        RusotoError::Service(DeleteFunctionError::TooManyRequests(_)) => true,
+                        _ => false,
+                    }
+                },
+            )
+            .map_err(Error::from),
+    )
+}
+
+/// Delete the given versions of each function, with at most `parallelism` deletions in flight
+///
+/// A version that fails to delete is logged and skipped, and the number of failures returned
+pub fn purge_versions(
+    credentials: Credentials,
+    region: Region,
+    purges: &[(Func, Vec<FunctionConfiguration>)],
+    retries: Retries,
+    parallelism: usize,
+) -> Box<dyn Future<Item = usize, Error = Error> + Send> {
+    let clients = match regional_clients(credentials, &region, purges.iter().map(|(func, _)| func))
+    {
+        Ok(clients) => clients,
+        Err(name) => return Box::new(future::err(Error::Fips(name))),
+    };
+    let deletions = purges
+        .iter()
+        .flat_map(|(func, versions)| {
+            let client = clients[func.region().unwrap_or_else(|| region.clone()).name()].clone();
+            let name = func.name().unwrap_or_default();
+            versions.iter().map(move |version| {
+                (
+                    client.clone(),
+                    name.clone(),
+                    version.version.clone().unwrap_or_default(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    Box::new(
+        stream::iter_ok(deletions)
+            .map(move |(client, name, version)| {
+                delete_version(client, name.clone(), version.clone(), retries.clone()).then(
+                    move |result| {
+                        if let Err(err) = &result {
+                            log::warn!("failed to delete {} version {}: {}", name, version, err);
+                        }
+                        future::ok::<_, Error>(result.is_err())
+                    },
+                )
+            })
+            .buffer_unordered(parallelism)
+            .fold(0, |failures, failed| {
+                future::ok::<_, Error>(failures + failed as usize)
+            }),
+    )
+}
+
+/// Split a versioned layer ARN into the ARN of the layer and its version number
+fn layer_version(arn: &str) -> Option<(&str, i64)> {
+    let pos = arn.rfind(':')?;
+    Some((&arn[..pos], arn[pos + 1..].parse().ok()?))
+}
+
+/// Fetch the latest version number of a layer, `None` when it has no versions left
+pub fn latest_layer_version(
+    client: LambdaClient,
+    layer_arn: String,
+    retries: Retries,
+) -> impl Future<Item = Option<i64>, Error = Error> + Send {
+    paginate("layer version", MAX_PAGES, move |marker| {
+        let client = client.clone();
+        let layer_arn = layer_arn.clone();
+        let limiter = retries.limiter.clone();
+        backoff(&retries)
+            .retry_if(
+                move || {
+                    limiter.limit(client.list_layer_versions(ListLayerVersionsRequest {
+                        layer_name: layer_arn.clone(),
+                        marker: marker.clone(),
+                        ..ListLayerVersionsRequest::default()
+                    }))
+                },
+                |err: &RusotoError<ListLayerVersionsError>| {
+                    log::debug!("lambda api error {}", err);
+                    match err {
+                        RusotoError::Service(ListLayerVersionsError::TooManyRequests(_)) => true,
+                        _ => false,
+                    }
+                },
+            )
+            .map(|result| {
+                (
+                    result.layer_versions.unwrap_or_default(),
+                    result.next_marker,
+                )
+            })
+            .map_err(Error::from)
+    })
+    .fold(None, |latest, page| {
+        future::ok::<_, Error>(page.into_iter().filter_map(|item| item.version).fold(
+            latest,
+            |latest: Option<i64>, version| match latest {
+                Some(latest) if latest >= version => Some(latest),
+                _ => Some(version),
+            },
+        ))
+    })
+}
+
+/// A function attached to an older version of a layer than its latest
+#[derive(Debug, PartialEq)]
+pub struct StaleLayer {
+    pub name: String,
+    pub layer: String,
+    pub version: i64,
+    pub latest: i64,
+}
+
+/// Find functions attached to older versions of layers than the given latest versions
+pub fn find_stale_layers(
+    funcs: &[Func],
+    latest: &HashMap<String, i64>,
+) -> Vec<StaleLayer> {
+    let mut stale = Vec::new();
+    for func in funcs {
+        for arn in func.layers() {
+            let (layer, version) = match layer_version(&arn) {
+                Some(layer_version) => layer_version,
+                None => continue,
+            };
+            match latest.get(layer) {
+                Some(&latest) if latest > version => stale.push(StaleLayer {
+                    name: func.name().unwrap_or_else(|| "-".into()),
+                    layer: layer.into(),
+                    version,
+                    latest,
+                }),
+                _ => (),
+            }
+        }
+    }
+    stale
+}
+
+/// Look up the latest version of each layer attached to functions, once per layer, and find
+/// functions lagging behind, with at most `parallelism` layers looked up at once
+///
+/// Layers that can't be looked up, e.g. those shared from other accounts without permission to
+/// list their versions, are logged and skipped
+pub fn stale_layers(
+    credentials: Credentials,
+    region: Region,
+    funcs: Vec<Func>,
+    retries: Retries,
+    parallelism: usize,
+) -> Box<dyn Future<Item = Vec<StaleLayer>, Error = Error> + Send> {
+    let layers = funcs
+        .iter()
+        .flat_map(Func::layers)
+        .filter_map(|arn| layer_version(&arn).map(|(layer, _)| layer.to_string()))
+        .collect::<BTreeSet<_>>();
+    let clients = match region_clients(
+        credentials,
+        &region,
+        layers
+            .iter()
+            .map(|layer| layer_region(layer).unwrap_or_else(|| region.clone())),
+    ) {
+        Ok(clients) => clients,
+        Err(name) => return Box::new(future::err(Error::Fips(name))),
+    };
+    Box::new(
+        stream::iter_ok(layers)
+            .map(move |layer| {
+                let client = clients[layer_region(&layer)
+                    .unwrap_or_else(|| region.clone())
+                    .name()]
+                .clone();
+                latest_layer_version(client, layer.clone(), retries.clone()).then(|result| {
+                    match result {
+                        Ok(latest) => future::ok::<_, Error>(latest.map(|latest| (layer, latest))),
+                        Err(err) => {
+                            log::warn!("skipping layer {}: {}", layer, err);
+                            future::ok(None)
+                        }
+                    }
+                })
+            })
+            .buffered(parallelism)
+            .filter_map(|latest| latest)
+            .collect()
+            .map(move |latest| find_stale_layers(&funcs, &latest.into_iter().collect())),
+    )
+}
+
+/// Parse the region of a layer from its ARN
+fn layer_region(arn: &str) -> Option<Region> {
+    arn.split(':').nth(3).and_then(|region| region.parse().ok())
+}
+
+/// Create a lambda client for each of the given regions and the provided one, keyed by
+/// region name
+///
+/// When the provided region is marked by `fips_region`, every other region's client connects
+/// to its FIPS endpoint too, failing with the name of a region without one rather than
+/// quietly sending requests to a standard endpoint
+fn region_clients(
+    credentials: Credentials,
+    region: &Region,
+    regions: impl IntoIterator<Item = Region>,
+) -> Result<HashMap<String, LambdaClient>, String> {
+    let mut clients = HashMap::new();
+    clients.insert(
+        region.name().to_string(),
+        lambda_client(credentials.clone(), region.clone()),
+    );
+    for other in regions {
+        if clients.contains_key(other.name()) {
+            continue;
+        }
+        let other = if is_fips(region) {
+            fips_region(other)?
+        } else {
+            other
+        };
+        clients.insert(
+            other.name().to_string(),
+            lambda_client(credentials.clone(), other),
+        );
+    }
+    Ok(clients)
+}
+
+/// Create a lambda client for each region the given functions live in, keyed by region name,
+/// as `region_clients` does
+fn regional_clients<'a>(
+    credentials: Credentials,
+    region: &Region,
+    funcs: impl IntoIterator<Item = &'a Func>,
+) -> Result<HashMap<String, LambdaClient>, String> {
+    region_clients(
+        credentials,
+        region,
+        funcs
+            .into_iter()
+            .map(|func| func.region().unwrap_or_else(|| region.clone())),
+    )
+}
+
+/// Return true for Lambda API errors worth retrying, i.e. throttling and server side failures
+fn lambdas_retryable(err: &RusotoError<ListFunctionsError>) -> bool {
+    match err {
+        RusotoError::Service(ListFunctionsError::TooManyRequests(_))
+        | RusotoError::Service(ListFunctionsError::Service(_)) => true,
+        RusotoError::Unknown(response) => response.status.is_server_error(),
+        _ => false,
+    }
+}
+
+/// Return true for tagging API errors worth retrying, i.e. throttling
+fn tags_retryable(err: &RusotoError<GetResourcesError>) -> bool {
+    match err {
+        RusotoError::Service(GetResourcesError::Throttled(_)) => true,
+        _ => false,
+    }
+}
+
+/// Stream pages of lambda tag mappings as they are fetched
+pub fn tag_mapping_pages(
+    client: ResourceGroupsTaggingApiClient,
+    tag_filters: Option<Vec<TagFilter>>,
+    page_size: i64,
+    retries: Retries,
+) -> impl Stream<Item = Vec<ResourceTagMapping>, Error = Error> + Send {
+    paginate("tagging", MAX_PAGES, move |token| {
+        let client = client.clone();
+        let tag_filters = tag_filters.clone();
+        let limiter = retries.limiter.clone();
+        backoff(&retries)
+            .retry_if(
+                move || {
+                    limiter.limit(client.get_resources(GetResourcesInput {
+                        resource_type_filters: Some(vec!["lambda:function".into()]),
+                        resources_per_page: Some(page_size),
+                        pagination_token: token.clone(),
+                        tag_filters: tag_filters.clone(),
+                        ..GetResourcesInput::default()
+                    }))
+                },
+                |err: &RusotoError<GetResourcesError>| {
+                    log::debug!("tagging api error {}", err);
+                    tags_retryable(err)
+                },
+            )
+            .map(|result| {
+                (
+                    result.resource_tag_mapping_list.unwrap_or_default(),
+                    result.pagination_token,
+                )
+            })
+            .map_err(Error::from)
+    })
+}
+
+pub fn tag_mappings(
+    client: ResourceGroupsTaggingApiClient,
+    tag_filters: Option<Vec<TagFilter>>,
+    page_size: i64,
+    retries: Retries,
+    progress: Progress,
+) -> Box<dyn Future<Item = Vec<ResourceTagMapping>, Error = Error> + Send> {
+    Box::new(
+        tag_mapping_pages(client, tag_filters, page_size, retries)
+            .inspect(move |page| progress.mappings(page.len()))
+            .concat2(),
+    )
+}
+
+/// Running count of fetched resources, drawn as a spinner on stderr when enabled
+#[derive(Clone)]
+pub struct Progress {
+    bar: ProgressBar,
+    lambdas: Arc<AtomicUsize>,
+    mappings: Arc<AtomicUsize>,
+}
+
+impl Progress {
+    pub fn new(enabled: bool) -> Self {
+        let bar = if enabled {
+            let bar = ProgressBar::new_spinner();
+            bar.enable_steady_tick(100);
+            bar
+        } else {
+            ProgressBar::hidden()
+        };
+        Progress {
+            bar,
+            lambdas: Arc::default(),
+            mappings: Arc::default(),
+        }
+    }
+
+    fn lambdas(
+        &self,
+        count: usize,
+    ) {
+        self.lambdas.fetch_add(count, Ordering::SeqCst);
+        self.update()
+    }
+
+    fn mappings(
+        &self,
+        count: usize,
+    ) {
+        self.mappings.fetch_add(count, Ordering::SeqCst);
+        self.update()
+    }
+
+    fn update(&self) {
+        self.bar.set_message(&self.message())
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "fetched {} functions / {} tag mappings",
+            self.lambdas.load(Ordering::SeqCst),
+            self.mappings.load(Ordering::SeqCst)
+        )
+    }
+
+    /// Clear the spinner once the given fetch completes, before anything is rendered
+    pub fn finish_after<F>(
+        self,
+        future: F,
+    ) -> impl Future<Item = F::Item, Error = F::Error> + Send
+    where
+        F: Future + Send,
+        F::Item: Send,
+        F::Error: Send,
+    {
+        future.then(move |result| {
+            self.finish();
+            result
+        })
+    }
+
+    /// Clear the spinner, e.g. when a fetch is cancelled before it completes
+    pub fn finish(&self) {
+        self.bar.finish_and_clear()
+    }
+}
+
+/// Pair each lambda configuration with the tags of its tagged resource
+///
+/// Tag mappings without a lambda are always dropped. Lambdas without a tag mapping are
+/// kept with no tags when `keep_untagged` is true, which is only meaningful when the
+/// mappings were fetched without tag filters
+pub fn join(
+    mappings: Vec<ResourceTagMapping>,
+    lambdas: Vec<FunctionConfiguration>,
+    keep_untagged: bool,
+) -> Vec<Func> {
+    let mut lookup: HashMap<String, Vec<Tag>> = mappings
+        .into_iter()
+        .map(|mapping| {
+            (
+                mapping.resource_arn.unwrap_or_default(),
+                mapping.tags.unwrap_or_default(),
+            )
+        })
+        .collect();
+    lambdas.into_iter().fold(Vec::new(), |mut result, config| {
+        match lookup.remove(config.function_arn.as_deref().unwrap_or_default()) {
+            Some(tags) => result.push(Func {
+                config,
+                tags,
+                reserved_concurrency: None,
+            }),
+            None if keep_untagged => result.push(Func {
+                config,
+                tags: Vec::new(),
+                reserved_concurrency: None,
+            }),
+            None => (),
+        }
+        result
+    })
+}
+
+/// Fetch all lambdas matching the provided tags, or every lambda when no tags are provided
+///
+/// Progress is cleared once both lambdas and tag mappings have been fetched
+pub fn funcs(
+    credentials: Credentials,
+    region: Region,
+    tags: Vec<(String, String)>,
+    lambda_page_size: i64,
+    tags_page_size: i64,
+    retries: Retries,
+    progress: Progress,
+) -> Box<dyn Future<Item = Vec<Func>, Error = Error> + Send> {
+    Box::new(progress.clone().finish_after(region_funcs(
+        credentials,
+        region,
+        tags,
+        lambda_page_size,
+        tags_page_size,
+        retries,
+        progress,
+    )))
+}
+
+/// Where lambdas are fetched from, the AWS APIs or a fixture file standing in for them
+pub trait Source: Send + Sync {
+    /// Fetch the lambdas matching the provided tags, or every lambda when no tags are provided,
+    /// as `region_funcs` does
+    fn region_funcs(
+        &self,
+        tags: Vec<(String, String)>,
+        progress: Progress,
+    ) -> Box<dyn Future<Item = Vec<Func>, Error = Error> + Send>;
+
+    /// Fetch lambdas as `region_funcs` does, clearing progress once they are fetched
+    fn funcs(
+        &self,
+        tags: Vec<(String, String)>,
+        progress: Progress,
+    ) -> Box<dyn Future<Item = Vec<Func>, Error = Error> + Send> {
+        Box::new(
+            progress
+                .clone()
+                .finish_after(self.region_funcs(tags, progress)),
+        )
+    }
+}
+
+/// Lambdas listed from the Lambda and tagging APIs of a region
+pub struct Live {
+    pub credentials: Credentials,
+    pub region: Region,
+    pub lambda_page_size: i64,
+    pub tags_page_size: i64,
+    pub retries: Retries,
+}
+
+impl Source for Live {
+    fn region_funcs(
+        &self,
+        tags: Vec<(String, String)>,
+        progress: Progress,
+    ) -> Box<dyn Future<Item = Vec<Func>, Error = Error> + Send> {
+        Box::new(region_funcs(
+            self.credentials.clone(),
+            self.region.clone(),
+            tags,
+            self.lambda_page_size,
+            self.tags_page_size,
+            self.retries.clone(),
+            progress,
+        ))
+    }
+}
+
+/// Lambdas loaded from a file instead of AWS, for demos and tests
+///
+/// The file is a JSON array of GetFunction responses, each with a `Configuration` in the shape
+/// of the Lambda API and optional `Tags`, e.g. `[{"Configuration": {"FunctionName": "api"},
+/// "Tags": {"team": "payments"}}]`. Tag filters match as the tagging API does
+pub struct Fixture(Vec<GetFunctionResponse>);
+
+impl FromStr for Fixture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+            .map(Fixture)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Source for Fixture {
+    fn region_funcs(
+        &self,
+        tags: Vec<(String, String)>,
+        _: Progress,
+    ) -> Box<dyn Future<Item = Vec<Func>, Error = Error> + Send> {
+        let filters = filters(tags);
+        let funcs = self
+            .0
+            .iter()
+            .cloned()
+            .map(Func::from)
+            .filter(|func| {
+                filters.iter().all(|filter| {
+                    func.tags.iter().any(|tag| {
+                        filter.key.as_ref() == Some(&tag.key)
+                            && filter
+                                .values
+                                .iter()
+                                .all(|values| values.contains(&tag.value))
+                    })
+                })
+            })
+            .collect();
+        Box::new(future::ok(funcs))
+    }
+}
+
+/// Lambdas saved by `list --snapshot-out` with the time they were fetched, to analyse again
+/// without querying AWS
+///
+/// The file is a JSON object with a `captured_at` RFC 3339 timestamp and the `functions` in the
+/// same GetFunction shape a fixture file uses
+pub struct Snapshot {
+    pub captured_at: String,
+    pub fixture: Fixture,
+}
+
+#[derive(Deserialize)]
+struct SnapshotFile {
+    captured_at: String,
+    functions: Vec<GetFunctionResponse>,
+}
+
+impl FromStr for Snapshot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str::<SnapshotFile>(s)
+            .map(|file| Snapshot {
+                captured_at: file.captured_at,
+                fixture: Fixture(file.functions),
+            })
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Return a function in the shape of a GetFunction response, which rusoto can only read
+fn function_json(func: &Func) -> serde_json::Value {
+    let config = &func.config;
+    json!({
+        "Configuration": {
+            "CodeSha256": config.code_sha_256,
+            "CodeSize": config.code_size,
+            "DeadLetterConfig": config.dead_letter_config.as_ref().map(|dlq| json!({
+                "TargetArn": dlq.target_arn,
+            })),
+            "Description": config.description,
+            "Environment": config.environment.as_ref().map(|env| json!({
+                "Error": env.error.as_ref().map(|err| json!({
+                    "ErrorCode": err.error_code,
+                    "Message": err.message,
+                })),
+                "Variables": env.variables,
+            })),
+            "FunctionArn": config.function_arn,
+            "FunctionName": config.function_name,
+            "Handler": config.handler,
+            "KMSKeyArn": config.kms_key_arn,
+            "LastModified": config.last_modified,
+            "Layers": config.layers.as_ref().map(|layers| {
+                layers
+                    .iter()
+                    .map(|layer| json!({"Arn": layer.arn, "CodeSize": layer.code_size}))
+                    .collect::<Vec<_>>()
+            }),
+            "MasterArn": config.master_arn,
+            "MemorySize": config.memory_size,
+            "RevisionId": config.revision_id,
+            "Role": config.role,
+            "Runtime": config.runtime,
+            "Timeout": config.timeout,
+            "TracingConfig": config.tracing_config.as_ref().map(|tracing| json!({
+                "Mode": tracing.mode,
+            })),
+            "Version": config.version,
+            "VpcConfig": config.vpc_config.as_ref().map(|vpc| json!({
+                "SecurityGroupIds": vpc.security_group_ids,
+                "SubnetIds": vpc.subnet_ids,
+                "VpcId": vpc.vpc_id,
+            })),
+        },
+        "Concurrency": func.reserved_concurrency.map(|reserved| json!({
+            "ReservedConcurrentExecutions": reserved,
+        })),
+        "Tags": func
+            .tags
+            .iter()
+            .map(|tag| (tag.key.clone(), tag.value.clone()))
+            .collect::<BTreeMap<_, _>>(),
+    })
+}
+
+/// Write functions as a snapshot `Snapshot` can load, stamped with the time they were fetched
+pub fn write_snapshot(
+    out: &mut impl Write,
+    funcs: &[Func],
+    captured_at: DateTime<Utc>,
+) -> serde_json::Result<()> {
+    serde_json::to_writer_pretty(
+        out,
+        &json!({
+            "captured_at": captured_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "functions": funcs.iter().map(function_json).collect::<Vec<_>>(),
+        }),
+    )
+}
+
+/// Names of the regions a multi-region fetch skipped, with the error each failed with
+pub type RegionFailures = Vec<(String, Error)>;
+
+/// Fetch lambdas from each of the given regions concurrently, listing each region the way
+/// `live` lists its own
+///
+/// Every region is scanned with the credentials, page sizes and retries of `live`.
+/// `region_concurrency` limits how many regions are scanned at once, each starting once a
+/// slot frees up, which bounds open connections and credential lookups however many regions
+/// are given. This is separate from the limiter in `live`'s retries, which caps the API
+/// requests in flight across every region. Regions that fail are skipped and returned
+/// alongside the lambdas of the rest, failing with `Error::AllRegions` carrying each region's
+/// error only when every region fails
+pub fn all_region_funcs(
+    live: &Live,
+    regions: Vec<Region>,
+    tags: Vec<(String, String)>,
+    region_concurrency: usize,
+    progress: Progress,
+) -> Box<dyn Future<Item = (Vec<Func>, RegionFailures), Error = Error> + Send> {
+    let limiter = Limiter::new(Some(region_concurrency.max(1)));
+    let fetches = regions
+        .into_iter()
+        .map(|region| {
+            let name = region.name().to_string();
+            limiter
+                .limit(region_funcs(
+                    live.credentials.clone(),
+                    region,
+                    tags.clone(),
+                    live.lambda_page_size,
+                    live.tags_page_size,
+                    live.retries.clone(),
+                    progress.clone(),
+                ))
+                .then(move |result| future::ok::<_, Error>((name, result)))
+        })
+        .collect::<Vec<_>>();
+    Box::new(
+        progress
+            .finish_after(future::join_all(fetches))
+            .and_then(|results| {
+                let mut funcs = Vec::new();
+                let mut failures = Vec::new();
+                let mut succeeded = false;
+                for (region, result) in results {
+                    match result {
+                        Ok(region_funcs) => {
+                            succeeded = true;
+                            funcs.extend(region_funcs)
+                        }
+                        Err(err) => failures.push((region, err)),
+                    }
+                }
+                if !succeeded && !failures.is_empty() {
+                    return future::err(Error::AllRegions(failures));
+                }
+                future::ok((funcs, failures))
+            }),
+    )
+}
+
+/// Fetch and join the lambdas and tag mappings of a single region
+///
+/// Without tag filters every function is listed alongside the tag mappings. With them, the
+/// tag mappings are fetched first and, when only a few functions match, those are looked up
+/// individually instead of listing every function in the region.
+///
+/// Unlike `funcs`, progress is left running for callers combining several fetches
+pub fn region_funcs(
+    credentials: Credentials,
+    region: Region,
+    tags: Vec<(String, String)>,
+    lambda_page_size: i64,
+    tags_page_size: i64,
+    retries: Retries,
+    progress: Progress,
+) -> impl Future<Item = Vec<Func>, Error = Error> + Send {
+    let filtered = !tags.is_empty();
+    let tag_mappings = tag_mappings(
+        tags_client(credentials.clone(), region.clone()),
+        Some(filters(tags)),
+        tags_page_size,
+        retries.clone(),
+        progress.clone(),
+    );
+    let client = lambda_client(credentials, region);
+    if !filtered {
+        let lambdas = lambdas(client, lambda_page_size, retries, progress);
+        return future::Either::A(
+            tag_mappings
+                .join(lambdas)
+                .map(|(mappings, lambdas)| join(mappings, lambdas, true)),
+        );
+    }
+    future::Either::B(tag_mappings.and_then(move |mappings| {
+        let lambdas = if mappings.len() <= MAX_LOOKUPS {
+            let arns = mappings
+                .iter()
+                .filter_map(|mapping| mapping.resource_arn.clone())
+                .collect();
+            lambdas_by_arn(client, arns, retries, progress)
+        } else {
+            lambdas(client, lambda_page_size, retries, progress)
+        };
+        lambdas.map(move |lambdas| join(mappings, lambdas, false))
+    }))
+}
+
+/// Compare function names, breaking case insensitive ties by case so the order is stable
+fn compare_names(
+    a: &str,
+    b: &str,
+    natural: bool,
+    ignore_case: bool,
+) -> cmp::Ordering {
+    match (natural, ignore_case) {
+        (true, true) => natord::compare_ignore_case(a, b).then_with(|| natord::compare(a, b)),
+        (true, false) => natord::compare(a, b),
+        (false, true) => a
+            .to_lowercase()
+            .cmp(&b.to_lowercase())
+            .then_with(|| a.cmp(b)),
+        (false, false) => a.cmp(b),
+    }
+}
+
+/// Sort functions in place by the given key, descending if `reverse` is true
+///
+/// With `natural`, names are compared numeric aware so `worker-2` sorts before `worker-10`.
+/// With `ignore_case`, names differing only in case sort adjacently. Ties are broken by name and
+/// then ARN, ascending even when reversed, so output is the same from run to run
+pub fn sort_funcs(
+    funcs: &mut [Func],
+    sort: Sort,
+    reverse: bool,
+    natural: bool,
+    ignore_case: bool,
+) {
+    funcs.sort_unstable_by(|a, b| {
+        let ordering = match sort {
+            Sort::Name => compare_names(
+                &a.name().unwrap_or_default(),
+                &b.name().unwrap_or_default(),
+                natural,
+                ignore_case,
+            ),
+            Sort::CodeSize => a
+                .code_size()
+                .unwrap_or_default()
+                .cmp(&b.code_size().unwrap_or_default()),
+            Sort::Runtime => a
+                .runtime()
+                .unwrap_or_default()
+                .cmp(&b.runtime().unwrap_or_default()),
+            Sort::Memory => a
+                .memory_size()
+                .unwrap_or_default()
+                .cmp(&b.memory_size().unwrap_or_default()),
+            Sort::LastModified => a.last_modified().cmp(&b.last_modified()),
+            Sort::TagCount => a.tags.len().cmp(&b.tags.len()),
+        };
+        let ordering = if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering
+            .then_with(|| {
+                compare_names(
+                    &a.name().unwrap_or_default(),
+                    &b.name().unwrap_or_default(),
+                    natural,
+                    ignore_case,
+                )
+            })
+            .then_with(|| a.arn().cmp(&b.arn()))
+    })
+}
+
+/// Layout of text output
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TextLayout {
+    /// Whether text, csv, markdown and table output start with a header row
+    pub header: bool,
+    /// Characters the tags column is truncated to, if any
+    pub tags_width: Option<usize>,
+    /// Spaces between columns
+    pub padding: usize,
+    /// Minimum width of each column, before padding
+    pub min_width: usize,
+}
+
+impl Default for TextLayout {
+    /// The layout of `TabWriter::new`, with a header and without truncating tags
+    fn default() -> Self {
+        TextLayout {
+            header: true,
+            tags_width: None,
+            padding: 2,
+            min_width: 2,
+        }
+    }
+}
+
+pub fn render_funcs(
+    out: &mut impl Write,
+    funcs: &[Func],
+    output: Output,
+    columns: &[Column],
+    units: Units,
+    layout: TextLayout,
+) {
+    match output {
+        Output::Text => render_funcs_text(out, funcs, columns, units, layout),
+        Output::Csv => render_funcs_csv(out, funcs, columns, units, layout.header),
+        Output::Tsv => render_funcs_tsv(out, funcs, columns, units, layout.header),
+        Output::Yaml => render_funcs_yaml(out, funcs, units),
+        Output::Markdown => render_funcs_markdown(out, funcs, columns, units, layout.header),
+        Output::Prometheus => render_funcs_prometheus(out, funcs),
+        Output::Table => render_funcs_table(out, funcs, columns, units, layout.header),
+        Output::Jsonl => render_funcs_jsonl(out, funcs, units),
+    }
+}
+
+/// Return the value of a column for a given function, highlighted for terminal display
+fn text_cell(
+    column: Column,
+    func: &Func,
+    units: Units,
+    tags_width: Option<usize>,
+) -> ColoredString {
+    match column {
+        Column::Tags => match tags_width {
+            Some(width) => truncate(&column.value(func, units), width).normal(),
+            None => column.value(func, units).normal(),
+        },
+        Column::Runtime if func.runtime_suggestion().is_some() => format!(
+            "{} (deprecated, see --go-migration)",
+            column.value(func, units)
+        )
+        .cyan(),
+        Column::Runtime if func.is_deprecated_runtime() => {
+            format!("{} (deprecated)", column.value(func, units)).cyan()
+        }
+        Column::Runtime => column.value(func, units).cyan(),
+        Column::Size if func.code_size().unwrap_or_default() > LARGE_CODE_SIZE => {
+            column.value(func, units).red()
+        }
+        _ => column.value(func, units).normal(),
+    }
+}
+
+/// Shorten a value to at most `width` characters, marking truncation with an ellipsis
+fn truncate(
+    value: &str,
+    width: usize,
+) -> String {
+    if value.chars().count() <= width {
+        return value.into();
+    }
+    value
+        .chars()
+        .take(width.saturating_sub(1))
+        .chain(Some('…'))
+        .collect()
+}
+
+fn render_funcs_text(
+    out: &mut impl Write,
+    funcs: &[Func],
+    columns: &[Column],
+    units: Units,
+    layout: TextLayout,
+) {
+    let columns = if columns.is_empty() {
+        Column::defaults()
+    } else {
+        columns.to_vec()
+    };
+    let mut writer = TabWriter::new(out)
+        .padding(layout.padding)
+        .minwidth(layout.min_width);
+    if layout.header {
+        drop(writeln!(
+            &mut writer,
+            "{}",
+            columns
+                .iter()
+                .map(|column| column.to_string().to_uppercase())
+                .collect::<Vec<_>>()
+                .join("\t")
+        ));
+    }
+    for func in funcs {
+        drop(writeln!(
+            &mut writer,
+            "{}",
+            columns
+                .iter()
+                .map(|column| text_cell(*column, func, units, layout.tags_width).to_string())
+                .collect::<Vec<_>>()
+                .join("\t")
+        ));
+    }
+    drop(writer.flush())
+}
+
+/// Part of a parsed output template
+#[derive(Debug, PartialEq, Clone)]
+enum Piece {
+    Literal(String),
+    Field(Column),
+}
+
+/// A single line layout rendered per function, e.g. `{name}\t{runtime}\t{memory}`
+///
+/// Placeholders name columns, `{{` and `}}` write literal braces, and `\t`, `\n` and `\\`
+/// write a tab, newline and backslash
+#[derive(Debug, PartialEq, Clone)]
+pub struct Template(Vec<Piece>);
+
+impl Template {
+    /// Columns referenced by the template's placeholders
+    pub fn columns(&self) -> Vec<Column> {
+        self.0
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Field(column) => Some(*column),
+                Piece::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /// Interpolate the template for one function
+    pub fn render(
+        &self,
+        func: &Func,
+        units: Units,
+    ) -> String {
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                Piece::Literal(text) => text.clone(),
+                Piece::Field(column) => column.value(func, units),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{')
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}')
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder {{{}", name)),
+                        }
+                    }
+                    let column = Column::from_str(name.trim()).map_err(|_| {
+                        format!(
+                            "unknown placeholder {{{}}}, expected one of {}",
+                            name,
+                            Column::variants().join(", ")
+                        )
+                    })?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(literal.split_off(0)));
+                    }
+                    pieces.push(Piece::Field(column))
+                }
+                '}' => return Err("unmatched }, write }} for a literal brace".into()),
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        literal.push('\t')
+                    }
+                    Some('n') => {
+                        chars.next();
+                        literal.push('\n')
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        literal.push('\\')
+                    }
+                    _ => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Template(pieces))
+    }
+}
+
+/// Operators of a `Select` comparison
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Equal,
+    NotEqual,
+    Match,
+    Greater,
+    Less,
+}
+
+/// Fields a `Select` expression can compare
+#[derive(Debug, PartialEq, Clone)]
+enum Field {
+    Name,
+    Runtime,
+    Handler,
+    Description,
+    Size,
+    Memory,
+    Timeout,
+    Tag(String),
+}
+
+impl Field {
+    fn variants() -> &'static [&'static str] {
+        &[
+            "name",
+            "runtime",
+            "handler",
+            "description",
+            "size",
+            "memory",
+            "timeout",
+        ]
+    }
+
+    fn parse(name: &str) -> Option<Field> {
+        match name {
+            "name" => Some(Field::Name),
+            "runtime" => Some(Field::Runtime),
+            "handler" => Some(Field::Handler),
+            "description" => Some(Field::Description),
+            "size" => Some(Field::Size),
+            "memory" => Some(Field::Memory),
+            "timeout" => Some(Field::Timeout),
+            _ if name.starts_with("tag:") && name.len() > 4 => Some(Field::Tag(name[4..].into())),
+            _ => None,
+        }
+    }
+
+    /// Return true for fields compared as numbers rather than text
+    fn numeric(&self) -> bool {
+        match self {
+            Field::Size | Field::Memory | Field::Timeout => true,
+            _ => false,
+        }
+    }
+
+    /// Parse a number to compare a numeric field with, sizes taking the same units as `--min-size`
+    fn number(
+        &self,
+        value: &str,
+    ) -> Result<i64, String> {
+        match self {
+            Field::Size => parse_size(value),
+            _ => value
+                .parse()
+                .map_err(|_| format!("invalid number: `{}` is not a whole number", value)),
+        }
+    }
+
+    fn text_of(
+        &self,
+        func: &Func,
+    ) -> Option<String> {
+        match self {
+            Field::Name => func.name(),
+            Field::Runtime => func.runtime(),
+            Field::Handler => func.config.handler.clone(),
+            Field::Description => func.config.description.clone(),
+            Field::Tag(key) => func
+                .tags
+                .iter()
+                .find(|tag| &tag.key == key)
+                .map(|tag| tag.value.clone()),
+            _ => None,
+        }
+    }
+
+    fn number_of(
+        &self,
+        func: &Func,
+    ) -> Option<i64> {
+        match self {
+            Field::Size => func.code_size(),
+            Field::Memory => func.memory_size(),
+            Field::Timeout => func.timeout(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Text(Field, Op, String),
+    Pattern(Field, Regex),
+    Number(Field, Op, i64),
+}
+
+impl Expr {
+    fn matches(
+        &self,
+        func: &Func,
+    ) -> bool {
+        match self {
+            Expr::Or(left, right) => left.matches(func) || right.matches(func),
+            Expr::And(left, right) => left.matches(func) && right.matches(func),
+            Expr::Text(field, op, value) => {
+                let matched = field.text_of(func).as_ref() == Some(value);
+                matched == (*op == Op::Equal)
+            }
+            Expr::Pattern(field, pattern) => field
+                .text_of(func)
+                .iter()
+                .any(|text| pattern.is_match(text)),
+            Expr::Number(field, op, value) => match field.number_of(func) {
+                Some(number) => match op {
+                    Op::Equal => number == *value,
+                    Op::NotEqual => number != *value,
+                    Op::Greater => number > *value,
+                    _ => number < *value,
+                },
+                None => *op == Op::NotEqual,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    Text(String),
+    Op(Op),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+/// Return true for characters of a bare field name or value, e.g. `tag:team` or `10MB`
+fn word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-.:/".contains(c)
+}
+
+/// Split a `Select` expression into tokens, each with the column it starts at
+fn select_tokens(s: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().enumerate().peekable();
+    while let Some((index, c)) = chars.next() {
+        let column = index + 1;
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Op(Op::Match),
+            '>' => Token::Op(Op::Greater),
+            '<' => Token::Op(Op::Less),
+            '=' | '!' | '&' | '|' => {
+                let second = if c == '!' { '=' } else { c };
+                if chars.peek().map(|(_, next)| *next) != Some(second) {
+                    return Err(format!("at column {}: expected {}{}", column, c, second));
+                }
+                chars.next();
+                match c {
+                    '=' => Token::Op(Op::Equal),
+                    '!' => Token::Op(Op::NotEqual),
+                    '&' => Token::And,
+                    _ => Token::Or,
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) if chars.peek().is_some() => {
+                            text.extend(chars.next().map(|(_, c)| c))
+                        }
+                        Some((_, c)) => text.push(c),
+                        None => return Err(format!("at column {}: unclosed string", column)),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.peek().cloned().filter(|(_, c)| word_char(*c)) {
+                    word.push(c);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+            c => return Err(format!("at column {}: unexpected {}", column, c)),
+        };
+        tokens.push((column, token));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of a `Select` expression, where `&&` binds
+/// tighter than `||`
+struct SelectParser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+}
+
+impl SelectParser {
+    /// Column of the next token, or just past the end of the expression
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map(|(column, _)| *column)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn next_is(
+        &mut self,
+        token: &Token,
+    ) -> bool {
+        let matched = self.tokens.get(self.position).map(|(_, next)| next) == Some(token);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.next_is(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while self.next_is(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.primary()?));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let column = self.column();
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                let column = self.column();
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(format!("at column {}: expected )", column)),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let field = Field::parse(&name).ok_or_else(|| {
+                    format!(
+                        "at column {}: unknown field {}, expected one of {} or tag:<key>",
+                        column,
+                        name,
+                        Field::variants().join(", ")
+                    )
+                })?;
+                self.comparison(field, name)
+            }
+            _ => Err(format!("at column {}: expected a field or (", column)),
+        }
+    }
+
+    fn comparison(
+        &mut self,
+        field: Field,
+        name: String,
+    ) -> Result<Expr, String> {
+        let column = self.column();
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => {
+                return Err(format!(
+                    "at column {}: expected one of ==, !=, ~, > or <",
+                    column
+                ))
+            }
+        };
+        let value_column = self.column();
+        let value = match self.next() {
+            Some(Token::Word(value)) | Some(Token::Text(value)) => value,
+            _ => return Err(format!("at column {}: expected a value", value_column)),
+        };
+        match (field.numeric(), op) {
+            (false, Op::Equal) | (false, Op::NotEqual) => Ok(Expr::Text(field, op, value)),
+            (false, Op::Match) => Regex::new(&value)
+                .map(|pattern| Expr::Pattern(field, pattern))
+                .map_err(|err| format!("at column {}: {}", value_column, err)),
+            (false, _) => Err(format!(
+                "at column {}: {} is text, compare it with ==, != or ~",
+                column, name
+            )),
+            (true, Op::Match) => Err(format!(
+                "at column {}: {} is a number, compare it with ==, !=, > or <",
+                column, name
+            )),
+            (true, _) => field
+                .number(&value)
+                .map(|number| Expr::Number(field, op, number))
+                .map_err(|err| format!("at column {}: {}", value_column, err)),
+        }
+    }
+}
+
+/// A filter expression over function fields, e.g.
+/// `runtime ~ "nodejs" && size > 10MB && tag:team == "payments"`
+///
+/// Comparisons are joined with `&&` and `||`, grouped with parentheses. Text fields, `name`,
+/// `runtime`, `handler`, `description` and `tag:<key>`, compare with `==`, `!=` or `~` for a
+/// regex match. Numeric fields, `size`, `memory` in MB and `timeout` in seconds, compare with
+/// `==`, `!=`, `>` or `<`. Values are quoted strings or bare words, and a field a function
+/// lacks matches only `!=`
+#[derive(Debug, Clone)]
+pub struct Select {
+    source: String,
+    expr: Box<Expr>,
+}
+
+impl Select {
+    /// Return true if a function satisfies the expression
+    pub fn matches(
+        &self,
+        func: &Func,
+    ) -> bool {
+        self.expr.matches(func)
+    }
+}
+
+/// Compiled regexes can't be compared, so expressions are equal when written the same
+impl PartialEq for Select {
+    fn eq(
+        &self,
+        other: &Select,
+    ) -> bool {
+        self.source == other.source
+    }
+}
+
+impl FromStr for Select {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = SelectParser {
+            tokens: select_tokens(s)?,
+            position: 0,
+            end: s.chars().count() + 1,
+        };
+        let expr = parser.or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(format!("at column {}: expected && or ||", parser.column()));
+        }
+        Ok(Select {
+            source: s.into(),
+            expr: Box::new(expr),
+        })
+    }
+}
+
+/// Render a line per function by interpolating a template
+pub fn render_template(
+    out: &mut impl Write,
+    funcs: &[Func],
+    template: &Template,
+    units: Units,
+) {
+    for func in funcs {
+        drop(writeln!(out, "{}", template.render(func, units)));
+    }
+}
+
+/// Escape characters that would otherwise break a markdown table cell
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+fn render_funcs_markdown(
+    out: &mut impl Write,
+    funcs: &[Func],
+    columns: &[Column],
+    units: Units,
+    header: bool,
+) {
+    let columns = if columns.is_empty() {
+        Column::defaults()
+    } else {
+        columns.to_vec()
+    };
+    if header {
+        drop(writeln!(
+            out,
+            "| {} |",
+            columns
+                .iter()
+                .map(|column| column.to_string().to_uppercase())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+        drop(writeln!(
+            out,
+            "|{}|",
+            columns
+                .iter()
+                .map(|column| match column {
+                    Column::Size | Column::Memory | Column::Timeout => " ---: ",
+                    _ => " --- ",
+                })
+                .collect::<Vec<_>>()
+                .join("|")
+        ));
+    }
+    for func in funcs {
+        drop(writeln!(
+            out,
+            "| {} |",
+            columns
+                .iter()
+                .map(|column| markdown_cell(&column.value(func, units)))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+    }
+}
+
+/// Render the selected columns as a bordered table for reading in a terminal, sizing each
+/// column to its widest cell
+fn render_funcs_table(
+    out: &mut impl Write,
+    funcs: &[Func],
+    columns: &[Column],
+    units: Units,
+    header: bool,
+) {
+    let columns = if columns.is_empty() {
+        Column::defaults()
+    } else {
+        columns.to_vec()
+    };
+    let titles = columns
+        .iter()
+        .map(|column| column.to_string().to_uppercase())
+        .collect::<Vec<_>>();
+    let rows = funcs
+        .iter()
+        .map(|func| {
+            columns
+                .iter()
+                .map(|column| column.value(func, units).replace('\n', " "))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let widths = titles
+        .iter()
+        .enumerate()
+        .map(|(i, title)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .fold(if header { title.chars().count() } else { 0 }, usize::max)
+        })
+        .collect::<Vec<_>>();
+    let border = |left: &str, mid: &str, right: &str| {
+        format!(
+            "{}{}{}",
+            left,
+            widths
+                .iter()
+                .map(|width| "─".repeat(width + 2))
+                .collect::<Vec<_>>()
+                .join(mid),
+            right
+        )
+    };
+    let line = |cells: &[String]| {
+        format!(
+            "│{}│",
+            cells
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (cell, width))| match columns[i] {
+                    Column::Size | Column::Memory | Column::Timeout => {
+                        format!(" {:>width$} ", cell, width = width)
+                    }
+                    _ => format!(" {:<width$} ", cell, width = width),
+                })
+                .collect::<Vec<_>>()
+                .join("│")
+        )
+    };
+    drop(writeln!(out, "{}", border("┌", "┬", "┐")));
+    if header {
+        drop(writeln!(out, "{}", line(&titles)));
+        drop(writeln!(out, "{}", border("├", "┼", "┤")));
+    }
+    for row in &rows {
+        drop(writeln!(out, "{}", line(row)));
+    }
+    drop(writeln!(out, "{}", border("└", "┴", "┘")));
+}
+
+/// Escape a label value per the Prometheus text exposition format
+fn prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render a gauge per function for each numeric setting, in the Prometheus text exposition format
+fn render_funcs_prometheus(
+    out: &mut impl Write,
+    funcs: &[Func],
+) {
+    render_gauge(
+        out,
+        funcs,
+        "lambda_code_size_bytes",
+        "Size of the function's deployment package in bytes",
+        Func::code_size,
+    );
+    render_gauge(
+        out,
+        funcs,
+        "lambda_memory_size_megabytes",
+        "Memory allocated to the function in megabytes",
+        Func::memory_size,
+    );
+    render_gauge(
+        out,
+        funcs,
+        "lambda_timeout_seconds",
+        "Maximum execution time of the function in seconds",
+        Func::timeout,
+    )
+}
+
+fn render_gauge(
+    out: &mut impl Write,
+    funcs: &[Func],
+    name: &str,
+    help: &str,
+    value: fn(&Func) -> Option<i64>,
+) {
+    drop(writeln!(out, "# HELP {} {}", name, help));
+    drop(writeln!(out, "# TYPE {} gauge", name));
+    for func in funcs {
+        if let Some(value) = value(func) {
+            drop(writeln!(
+                out,
+                "{}{{function=\"{}\",runtime=\"{}\"}} {}",
+                name,
+                prometheus_label(&func.name().unwrap_or_default()),
+                prometheus_label(&func.runtime().unwrap_or_default()),
+                value
+            ));
+        }
+    }
+}
+
+/// Return the header and rows of delimited output, csv or tsv, for the given columns, or
+/// raw sizes and limits when none were picked
+fn delimited_records(
+    funcs: &[Func],
+    columns: &[Column],
+    units: Units,
+) -> (Vec<String>, Vec<Vec<String>>) {
+    if columns.is_empty() {
+        let header = [
+            "name",
+            "runtime",
+            "code_size",
+            "human_size",
+            "memory_size",
+            "timeout",
+        ];
+        let rows = funcs
+            .iter()
+            .map(|func| {
+                vec![
+                    func.name().unwrap_or_default(),
+                    func.runtime().unwrap_or_default(),
+                    func.code_size().unwrap_or_default().to_string(),
+                    func.human_size(units),
+                    func.memory_size()
+                        .map(|size| size.to_string())
+                        .unwrap_or_default(),
+                    func.timeout()
+                        .map(|secs| secs.to_string())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+        (header.iter().map(|title| title.to_string()).collect(), rows)
+    } else {
+        let rows = funcs
+            .iter()
+            .map(|func| {
+                columns
+                    .iter()
+                    .map(|column| column.value(func, units))
+                    .collect()
+            })
+            .collect();
+        (columns.iter().map(Column::to_string).collect(), rows)
+    }
+}
+
+fn render_funcs_csv(
+    out: &mut impl Write,
+    funcs: &[Func],
+    columns: &[Column],
+    units: Units,
+    header: bool,
+) {
+    let (titles, rows) = delimited_records(funcs, columns, units);
+    let mut writer = csv::Writer::from_writer(out);
+    if header {
+        drop(writer.write_record(titles));
+    }
+    for row in rows {
+        drop(writer.write_record(row));
+    }
+    drop(writer.flush())
+}
+
+/// Write tab separated values without alignment padding, so `cut -f` splits them cleanly
+///
+/// Tsv has no quoting, so tabs and line breaks within a value are replaced with spaces
+fn render_funcs_tsv(
+    out: &mut impl Write,
+    funcs: &[Func],
+    columns: &[Column],
+    units: Units,
+    header: bool,
+) {
+    let (titles, rows) = delimited_records(funcs, columns, units);
+    let titles = if header { Some(titles) } else { None };
+    for row in titles.into_iter().chain(rows) {
+        let row = row
+            .iter()
+            .map(|value| value.replace(['\t', '\n', '\r'], " "))
+            .collect::<Vec<_>>();
+        drop(writeln!(out, "{}", row.join("\t")));
+    }
+}
+
+fn render_funcs_yaml(
+    out: &mut impl Write,
+    funcs: &[Func],
+    units: Units,
+) {
+    let records = funcs
+        .iter()
+        .map(|func| Record::new(func, units))
+        .collect::<Vec<_>>();
+    match serde_yaml::to_string(&records) {
+        Ok(yaml) => drop(writeln!(out, "{}", yaml)),
+        Err(err) => log::error!("failed to serialize functions: {}", err),
+    }
+}
+
+/// Render one JSON object per line, flushing after each so downstream consumers see
+/// functions as they are written
+fn render_funcs_jsonl(
+    out: &mut impl Write,
+    funcs: &[Func],
+    units: Units,
+) {
+    for func in funcs {
+        match serde_json::to_string(&Record::new(func, units)) {
+            Ok(json) => {
+                drop(writeln!(out, "{}", json));
+                drop(out.flush());
+            }
+            Err(err) => log::error!("failed to serialize function: {}", err),
+        }
+    }
+}
+
+/// Render each function alongside the versioned ARNs of its attached layers
+pub fn render_layers(
+    out: &mut impl Write,
+    funcs: &[Func],
+) {
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tLAYERS"));
+    for func in funcs {
+        let name = func.name().unwrap_or_else(|| "-".into());
+        let layers = func.layers();
+        if layers.is_empty() {
+            drop(writeln!(&mut writer, "{}\t", name));
+        }
+        for (i, layer) in layers.into_iter().enumerate() {
+            drop(writeln!(
+                &mut writer,
+                "{}\t{}",
+                if i == 0 { name.as_str() } else { "" },
+                layer
+            ));
+        }
+    }
+    drop(writer.flush())
+}
+
+/// Render each function alongside its VPC id and the number of subnets and security groups
+pub fn render_vpcs(
+    out: &mut impl Write,
+    funcs: &[Func],
+) {
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tVPC\tSUBNETS\tSECURITY GROUPS"));
+    for func in funcs {
+        let (vpc, subnets, security_groups) = vpc_summary(func);
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}\t{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            vpc,
+            subnets,
+            security_groups
+        ));
+    }
+    drop(writer.flush())
+}
+
+/// Render only the functions with audit problems, naming each problem, and nothing at all
+/// when none have any
+pub fn render_problems(
+    out: &mut impl Write,
+    funcs: &[Func],
+) {
+    let problems = funcs
+        .iter()
+        .map(|func| (func, func.problems()))
+        .filter(|(_, problems)| !problems.is_empty())
+        .collect::<Vec<_>>();
+    if problems.is_empty() {
+        return;
+    }
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tPROBLEMS"));
+    for (func, problems) in problems {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            problems.join(", ")
+        ));
+    }
+    drop(writer.flush())
+}
+
+/// Render the functions on Go1.x with their handler and the runtime to migrate them to,
+/// printing nothing when there are none
+pub fn render_go_migration(
+    out: &mut impl Write,
+    funcs: &[Func],
+) {
+    let migrations = funcs
+        .iter()
+        .filter(|func| func.runtime().as_deref() == Some("go1.x"))
+        .filter_map(|func| Some((func, func.runtime_suggestion()?)))
+        .collect::<Vec<_>>();
+    if migrations.is_empty() {
+        return;
+    }
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tHANDLER\tMIGRATE TO"));
+    for (func, suggestion) in migrations {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            func.config.handler.as_deref().unwrap_or("-"),
+            suggestion
+        ));
+    }
+    drop(writer.flush())
+}
+
+/// Render the functions whose zipped code is within `pct` percent of the direct upload limit
+/// with the headroom each has left, warning when there are any and printing nothing otherwise
+pub fn render_near_limit(
+    out: &mut impl Write,
+    funcs: &[Func],
+    pct: f64,
+    units: Units,
+) {
+    let near = funcs
+        .iter()
+        .filter_map(|func| Some((func, func.upload_headroom(pct)?)))
+        .collect::<Vec<_>>();
+    if near.is_empty() {
+        return;
+    }
+    log::warn!(
+        "{} functions are within {}% of the {} direct upload limit, deploy them from S3 before they outgrow it",
+        near.len(),
+        pct,
+        units.format(DIRECT_UPLOAD_LIMIT)
+    );
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tSIZE\tHEADROOM"));
+    for (func, headroom) in near {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            func.human_size(units),
+            if headroom < 0 {
+                format!("over by {}", units.format(-headroom))
+            } else {
+                units.format(headroom)
+            }
+        ));
+    }
+    drop(writer.flush())
+}
+
+/// Render the functions whose timeout and memory both exceed the given limits with the reason
+/// each was flagged, warning when there are any and printing nothing otherwise
+pub fn render_outliers(
+    out: &mut impl Write,
+    funcs: &[Func],
+    timeout_secs: i64,
+    memory_mb: i64,
+) {
+    let outliers = funcs
+        .iter()
+        .filter_map(|func| Some((func, func.outlier_reason(timeout_secs, memory_mb)?)))
+        .collect::<Vec<_>>();
+    if outliers.is_empty() {
+        return;
+    }
+    log::warn!(
+        "{} functions combine a timeout over {}s with memory over {} MB, check they need both",
+        outliers.len(),
+        timeout_secs,
+        memory_mb
+    );
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tREASON"));
+    for (func, reason) in outliers {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            reason
+        ));
+    }
+    drop(writer.flush())
+}
+
+/// Return the VPC id, subnet count and security group count of a function for human display
+pub fn vpc_summary(func: &Func) -> (String, String, String) {
+    match func.vpc() {
+        Some(vpc) => (
+            vpc.vpc_id.clone().unwrap_or_default(),
+            vpc.subnet_ids
+                .as_ref()
+                .map(Vec::len)
+                .unwrap_or_default()
+                .to_string(),
+            vpc.security_group_ids
+                .as_ref()
+                .map(Vec::len)
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        None => ("-".into(), "-".into(), "-".into()),
+    }
+}
+
+/// Return the total code size of a set of functions for human display
+pub fn human_total_size(
+    funcs: &[Func],
+    units: Units,
+) -> String {
+    units.format(
+        funcs
+            .iter()
+            .map(|func| func.code_size().unwrap_or_default())
+            .sum::<i64>(),
+    )
+}
+
+/// Estimate the monthly cost of storing the code of functions at a US dollar price per GB
+pub fn estimated_storage_cost(
+    funcs: &[Func],
+    price_per_gb: f64,
+) -> f64 {
+    let total_size = funcs
+        .iter()
+        .map(|func| func.code_size().unwrap_or_default())
+        .sum::<i64>();
+    total_size as f64 / BYTES_PER_GB * price_per_gb
+}
+
+pub fn render_cost(
+    out: &mut impl Write,
+    funcs: &[Func],
+    price_per_gb: f64,
+) {
+    drop(writeln!(
+        out,
+        "estimated monthly storage cost: ${:.2} at ${} per GB",
+        estimated_storage_cost(funcs, price_per_gb),
+        price_per_gb
+    ))
+}
+
+/// Function count and total code size of a group of functions
+#[derive(Debug, Default, PartialEq)]
+pub struct Group {
+    pub count: usize,
+    pub code_size: i64,
+}
+
+/// Aggregate functions into groups, sorted by key
+///
+/// Functions without a value for the key are collected under `None`
+pub fn group_funcs(
+    funcs: &[Func],
+    group_by: GroupBy,
+) -> BTreeMap<Option<String>, Group> {
+    group_funcs_by(funcs, |func| group_by.key(func))
+}
+
+/// Aggregate functions by their value for a tag key, e.g. to attribute code size to teams
+///
+/// Functions without the tag are collected under `None`
+pub fn group_funcs_by_tag(
+    funcs: &[Func],
+    key: &str,
+) -> BTreeMap<Option<String>, Group> {
+    group_funcs_by(funcs, |func| func.tag_value(key))
+}
+
+fn group_funcs_by(
+    funcs: &[Func],
+    key: impl Fn(&Func) -> Option<String>,
+) -> BTreeMap<Option<String>, Group> {
+    let mut groups = BTreeMap::<Option<String>, Group>::new();
+    for func in funcs {
+        let group = groups.entry(key(func)).or_default();
+        group.count += 1;
+        group.code_size += func.code_size().unwrap_or_default();
+    }
+    groups
+}
+
+/// Return the whole percentage of their sum each count makes up, rounded so they add to 100
+///
+/// Each share is rounded down and the points left over go to the largest remainders
+fn percentages(counts: &[usize]) -> Vec<usize> {
+    let total = counts.iter().sum::<usize>();
+    if total == 0 {
+        return vec![0; counts.len()];
+    }
+    let mut shares = counts
+        .iter()
+        .map(|count| count * 100 / total)
+        .collect::<Vec<_>>();
+    let mut remainders = counts
+        .iter()
+        .enumerate()
+        .map(|(index, count)| (count * 100 % total, index))
+        .collect::<Vec<_>>();
+    remainders.sort_by(|(a, a_index), (b, b_index)| b.cmp(a).then(a_index.cmp(b_index)));
+    let left = 100 - shares.iter().sum::<usize>();
+    for (_, index) in remainders.into_iter().take(left) {
+        shares[index] += 1
+    }
+    shares
+}
+
+/// Render the count, total and average code size and share of functions of each group under
+/// a `label` column, followed by a total row, naming the group of functions without a key
+/// `missing`
+pub fn render_grouped(
+    out: &mut impl Write,
+    groups: &BTreeMap<Option<String>, Group>,
+    label: &str,
+    missing: &str,
+    units: Units,
+) {
+    let average = |count: usize, code_size: i64| {
+        if count == 0 {
+            0
+        } else {
+            code_size / count as i64
+        }
+    };
+    let shares = percentages(&groups.values().map(|group| group.count).collect::<Vec<_>>());
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(
+        &mut writer,
+        "{}\tCOUNT\tSIZE\tAVERAGE\tSHARE",
+        label.to_uppercase()
+    ));
+    for ((key, group), share) in groups.iter().zip(shares) {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}\t{}\t{}\t{}%",
+            key.as_ref().map(String::as_str).unwrap_or(missing),
+            group.count,
+            units.format(group.code_size),
+            units.format(average(group.count, group.code_size)),
+            share
+        ));
+    }
+    let count = groups.values().map(|group| group.count).sum::<usize>();
+    if count > 0 {
+        let code_size = groups.values().map(|group| group.code_size).sum::<i64>();
+        drop(writeln!(
+            &mut writer,
+            "total\t{}\t{}\t{}\t100%",
+            count,
+            units.format(code_size),
+            units.format(average(count, code_size))
+        ));
+    }
+    drop(writer.flush());
+}
+
+/// Count and total code size of a function's published versions
+#[derive(Debug, Default, PartialEq)]
+pub struct Versions {
+    pub count: usize,
+    pub code_size: i64,
+}
+
+impl<'a> From<&'a [FunctionConfiguration]> for Versions {
+    /// Aggregate version configurations, leaving out the unpublished `$LATEST`
+    fn from(versions: &'a [FunctionConfiguration]) -> Self {
+        versions
+            .iter()
+            .filter(|version| version.version.as_deref() != Some("$LATEST"))
+            .fold(Versions::default(), |mut total, version| {
+                total.count += 1;
+                total.code_size += version.code_size.unwrap_or_default();
+                total
+            })
+    }
+}
+
+/// Render each function's published version count and their total code size, highlighting
+/// functions with many versions
+pub fn render_versions(
+    out: &mut impl Write,
+    funcs: &[(Func, Versions)],
+    units: Units,
+) {
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tVERSIONS\tSIZE"));
+    for (func, versions) in funcs {
+        let count = if versions.count > MANY_VERSIONS {
+            versions.count.to_string().red()
+        } else {
+            versions.count.to_string().normal()
+        };
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            count,
+            units.format(versions.code_size)
+        ));
+    }
+    drop(writer.flush());
+}
+
+/// Total code size of functions and their published versions, as counted toward the quota
+pub fn code_storage(funcs: &[(Func, Versions)]) -> i64 {
+    funcs
+        .iter()
+        .map(|(func, versions)| func.code_size().unwrap_or_default() + versions.code_size)
+        .sum()
+}
+
+/// Render the versions selected for purging and a summary of the storage they free
+///
+/// `failures` is the number of versions that failed to delete once applied, with `None`
+/// describing what a dry run would delete
+pub fn render_purge(
+    out: &mut impl Write,
+    purges: &[(Func, Vec<FunctionConfiguration>)],
+    failures: Option<usize>,
+    units: Units,
+) {
+    let mut writer = TabWriter::new(&mut *out);
+    drop(writeln!(&mut writer, "NAME\tVERSION\tSIZE"));
+    let mut count = 0;
+    let mut size = 0;
+    for (func, versions) in purges {
+        for version in versions {
+            count += 1;
+            size += version.code_size.unwrap_or_default();
+            drop(writeln!(
+                &mut writer,
+                "{}\t{}\t{}",
+                func.name().unwrap_or_else(|| "-".into()),
+                version.version.clone().unwrap_or_else(|| "-".into()),
+                units.format(version.code_size.unwrap_or_default())
+            ));
+        }
+    }
+    drop(writer.flush());
+    match failures {
+        Some(0) => drop(writeln!(
+            out,
+            "deleted {} versions, freeing {}",
+            count,
+            units.format(size)
+        )),
+        Some(failures) => drop(writeln!(
+            out,
+            "deleted {} of {} versions, {} failed",
+            count - failures,
+            count,
+            failures
+        )),
+        None => drop(writeln!(
+            out,
+            "would delete {} versions, freeing {}, pass --apply to delete them",
+            count,
+            units.format(size)
+        )),
+    }
+}
+
+/// Render code storage against a quota in GB, warning when it is nearly used up
+pub fn render_storage(
+    out: &mut impl Write,
+    funcs: &[(Func, Versions)],
+    quota_gb: f64,
+    units: Units,
+) {
+    let storage = code_storage(funcs);
+    let used = storage as f64 / BYTES_PER_GB / quota_gb;
+    if used > CODE_STORAGE_WARNING {
+        log::warn!(
+            "code storage is {:.1}% of the {} GB quota, prune old versions before deploys fail",
+            used * 100.0,
+            quota_gb
+        )
+    }
+    drop(writeln!(
+        out,
+        "code storage: {} of {} GB ({:.1}%)",
+        units.format(storage),
+        quota_gb,
+        used * 100.0
+    ))
+}
+
+/// Fleet wide code size and tagging aggregates
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub total_size: i64,
+    pub average_size: i64,
+    pub median_size: i64,
+    pub untagged: usize,
+}
+
+impl<'a> From<&'a [Func]> for Stats {
+    fn from(funcs: &'a [Func]) -> Self {
+        let mut sizes = funcs
+            .iter()
+            .map(|func| func.code_size().unwrap_or_default())
+            .collect::<Vec<_>>();
+        sizes.sort();
+        let count = sizes.len();
+        let total_size = sizes.iter().sum::<i64>();
+        let median_size = match count {
+            0 => 0,
+            _ if count % 2 == 0 => (sizes[count / 2 - 1] + sizes[count / 2]) / 2,
+            _ => sizes[count / 2],
+        };
+        Stats {
+            count,
+            total_size,
+            average_size: if count == 0 {
+                0
+            } else {
+                total_size / count as i64
+            },
+            median_size,
+            untagged: funcs.iter().filter(|func| func.tags.is_empty()).count(),
+        }
+    }
+}
+
+pub fn render_stats(
+    out: &mut impl Write,
+    funcs: &[Func],
+    units: Units,
+) {
+    let stats = Stats::from(funcs);
+    let human = |size: i64| units.format(size);
+    let mut writer = TabWriter::new(&mut *out);
+    drop(writeln!(&mut writer, "Functions:\t{}", stats.count));
+    drop(writeln!(&mut writer, "Untagged:\t{}", stats.untagged));
+    drop(writeln!(
+        &mut writer,
+        "Total size:\t{}",
+        human(stats.total_size)
+    ));
+    drop(writeln!(
+        &mut writer,
+        "Average size:\t{}",
+        human(stats.average_size)
+    ));
+    drop(writeln!(
+        &mut writer,
+        "Median size:\t{}",
+        human(stats.median_size)
+    ));
+    drop(writer.flush());
+    drop(writer);
+    if !funcs.is_empty() {
+        drop(writeln!(out));
+        render_grouped(
+            out,
+            &group_funcs(funcs, GroupBy::Runtime),
+            "runtime",
+            "(none)",
+            units,
+        )
+    }
+}
+
+/// Functions found by only one of two queries, or by both, matched by ARN
+#[derive(Debug, Default)]
+pub struct FuncDiff {
+    pub left: Vec<Func>,
+    pub both: Vec<Func>,
+    pub right: Vec<Func>,
+}
+
+impl FuncDiff {
+    pub fn new(
+        left: Vec<Func>,
+        right: Vec<Func>,
+    ) -> Self {
+        let left_arns = left.iter().filter_map(Func::arn).collect::<HashSet<_>>();
+        let right_arns = right.iter().filter_map(Func::arn).collect::<HashSet<_>>();
+        let (both, left) = left.into_iter().partition(|func| match func.arn() {
+            Some(arn) => right_arns.contains(&arn),
+            None => false,
+        });
+        let right = right
+            .into_iter()
+            .filter(|func| match func.arn() {
+                Some(arn) => !left_arns.contains(&arn),
+                None => true,
+            })
+            .collect();
+        FuncDiff { left, both, right }
+    }
+}
+
+/// Render the names of each group of a diff under a heading labeling the group and its size
+pub fn render_diff(
+    out: &mut impl Write,
+    diff: &FuncDiff,
+    left_label: &str,
+    right_label: &str,
+) {
+    render_diff_group(out, &format!("only in {}", left_label), &diff.left);
+    render_diff_group(out, "in both", &diff.both);
+    render_diff_group(out, &format!("only in {}", right_label), &diff.right);
+}
+
+fn render_diff_group(
+    out: &mut impl Write,
+    label: &str,
+    funcs: &[Func],
+) {
+    drop(writeln!(out, "{} ({})", label, funcs.len()));
+    for func in funcs {
+        drop(writeln!(
+            out,
+            "  {}",
+            func.name().unwrap_or_else(|| "-".into())
+        ));
+    }
+}
+
+/// Render each function with its aliases and the versions they point to, `-` for none
+pub fn render_aliases(
+    out: &mut impl Write,
+    funcs: &[(Func, Vec<AliasConfiguration>)],
+) {
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tALIASES"));
+    for (func, aliases) in funcs {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            human_aliases(aliases)
+        ));
+    }
+    drop(writer.flush());
+}
+
+/// Format aliases as sorted `name=version` pairs
+fn human_aliases(aliases: &[AliasConfiguration]) -> String {
+    if aliases.is_empty() {
+        return "-".into();
+    }
+    aliases
+        .iter()
+        .map(|alias| {
+            (
+                alias.name.as_deref().unwrap_or("-"),
+                alias.function_version.as_deref().unwrap_or("-"),
+            )
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|(name, version)| format!("{}={}", name, version))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn render_stale_layers(
+    out: &mut impl Write,
+    stale: &[StaleLayer],
+) {
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "NAME\tLAYER\tVERSION\tLATEST"));
+    for layer in stale {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}\t{}\t{}",
+            layer.name, layer.layer, layer.version, layer.latest
+        ));
+    }
+    drop(writer.flush());
+}
+
+/// Render each function missing required tags, returning how many were
+pub fn render_missing_tags(
+    out: &mut impl Write,
+    funcs: &[Func],
+    required: &[String],
+) -> usize {
+    let mut writer = TabWriter::new(out);
+    let mut offenders = 0;
+    for func in funcs {
+        let missing = func.missing_tags(required);
+        if missing.is_empty() {
+            continue;
+        }
+        if offenders == 0 {
+            drop(writeln!(&mut writer, "NAME\tMISSING"));
+        }
+        offenders += 1;
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}",
+            func.name().unwrap_or_else(|| "-".into()),
+            missing.join(",")
+        ));
+    }
+    drop(writer.flush());
+    offenders
+}
+
+pub fn render_summary(
+    out: &mut impl Write,
+    funcs: &[Func],
+    units: Units,
+) {
+    drop(writeln!(
+        out,
+        "{} functions, {} total",
+        funcs.len(),
+        human_total_size(funcs, units)
+    ))
+}
+
+pub fn render_func_detail(
+    out: &mut impl Write,
+    func: &Func,
+    env: bool,
+    show_values: bool,
+    units: Units,
+) {
+    let placeholder = || "-".to_string();
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(
+        &mut writer,
+        "name:\t{}",
+        func.name().unwrap_or_else(placeholder)
+    ));
+    drop(writeln!(
+        &mut writer,
+        "arn:\t{}",
+        func.arn().unwrap_or_else(placeholder)
+    ));
+    drop(writeln!(
+        &mut writer,
+        "runtime:\t{}",
+        func.runtime().unwrap_or_else(placeholder)
+    ));
+    drop(writeln!(
+        &mut writer,
+        "handler:\t{}",
+        func.handler().unwrap_or_else(placeholder)
+    ));
+    drop(writeln!(&mut writer, "memory:\t{}", func.human_memory()));
+    drop(writeln!(&mut writer, "timeout:\t{}", func.human_timeout()));
+    drop(writeln!(
+        &mut writer,
+        "reserved concurrency:\t{}",
+        func.human_concurrency()
+    ));
+    drop(writeln!(
+        &mut writer,
+        "code size:\t{}",
+        func.human_size(units)
+    ));
+    drop(writeln!(
+        &mut writer,
+        "last modified:\t{}",
+        func.config
+            .last_modified
+            .clone()
+            .unwrap_or_else(placeholder)
+    ));
+    let (vpc, subnets, security_groups) = vpc_summary(func);
+    drop(writeln!(&mut writer, "vpc:\t{}", vpc));
+    drop(writeln!(&mut writer, "subnets:\t{}", subnets));
+    drop(writeln!(
+        &mut writer,
+        "security groups:\t{}",
+        security_groups
+    ));
+    drop(writeln!(
+        &mut writer,
+        "dead letter queue:\t{}",
+        func.dead_letter_target().unwrap_or_else(|| "none".into())
+    ));
+    let mut tags = func.tags.iter().collect::<Vec<_>>();
+    tags.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+    if tags.is_empty() {
+        drop(writeln!(&mut writer, "tags:\t{}", placeholder()));
+    }
+    for (i, tag) in tags.into_iter().enumerate() {
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}={}",
+            if i == 0 { "tags:" } else { "" },
+            tag.key,
+            tag.value
+        ));
+    }
+    if env {
+        let variables = func.env_vars();
+        if variables.is_empty() {
+            drop(writeln!(&mut writer, "environment:\t{}", placeholder()));
+        }
+        for (i, (key, value)) in variables.into_iter().enumerate() {
+            let label = if i == 0 { "environment:" } else { "" };
+            drop(if show_values {
+                writeln!(&mut writer, "{}\t{}={}", label, key, value)
+            } else {
+                writeln!(&mut writer, "{}\t{}", label, key)
+            });
+        }
+    }
+    drop(writer.flush())
+}
+
+/// Count the functions using each runtime, with image functions, which have none, under `image`
+pub fn runtime_counts(funcs: &[Func]) -> BTreeMap<String, usize> {
+    funcs.iter().fold(BTreeMap::new(), |mut counts, func| {
+        *counts
+            .entry(func.runtime().unwrap_or_else(|| "image".into()))
+            .or_insert(0) += 1;
+        counts
+    })
+}
+
+/// Render runtime counts, most used first
+pub fn render_runtimes(
+    out: &mut impl Write,
+    counts: BTreeMap<String, usize>,
+) {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "RUNTIME\tFUNCTIONS"));
+    for (runtime, count) in counts {
+        drop(writeln!(&mut writer, "{}\t{}", runtime, count));
+    }
+    drop(writer.flush())
+}
+
+/// Collect the distinct tag keys, and optionally their values, across tagged resources
+pub fn tag_names(
+    mappings: &[ResourceTagMapping],
+    values: bool,
+) -> BTreeSet<(String, Option<String>)> {
+    mappings.iter().fold(BTreeSet::new(), |mut names, mapping| {
+        for tag in mapping.tags.clone().unwrap_or_default() {
+            names.insert((tag.key, if values { Some(tag.value) } else { None }));
+        }
+        names
+    })
+}
+
+pub fn render_tags(
+    out: &mut impl Write,
+    tags: BTreeSet<(String, Option<String>)>,
+) {
+    for (key, value) in tags {
+        match value {
+            Some(value) => drop(writeln!(out, "{}={}", key, value)),
+            None => drop(writeln!(out, "{}", key)),
+        }
+    }
+}
+
+/// Render distinct tag pairs as aligned key and value columns, leaving the key blank on the
+/// rows continuing a key with more values
+pub fn render_tag_values(
+    out: &mut impl Write,
+    tags: BTreeSet<(String, String)>,
+) {
+    let mut writer = TabWriter::new(out);
+    drop(writeln!(&mut writer, "KEY\tVALUE"));
+    let mut previous: Option<String> = None;
+    for (key, value) in tags {
+        let continued = previous.as_ref() == Some(&key);
+        drop(writeln!(
+            &mut writer,
+            "{}\t{}",
+            if continued { "" } else { key.as_str() },
+            value
+        ));
+        previous = Some(key);
+    }
+    drop(writer.flush())
+}
+
+/// Source of AWS credentials used to sign requests
+#[derive(Clone)]
+pub enum Credentials {
+    Chain(Arc<AutoRefreshingProvider<ChainProvider>>),
+    Profile(Arc<AutoRefreshingProvider<NamedProfile>>),
+    AssumeRole(Arc<AutoRefreshingProvider<StsAssumeRoleSessionCredentialsProvider>>),
+}
+
+impl ProvideAwsCredentials for Credentials {
+    type Future = Box<dyn Future<Item = AwsCredentials, Error = CredentialsError> + Send>;
+
+    fn credentials(&self) -> Self::Future {
+        match self {
+            Credentials::Chain(chain) => Box::new(chain.credentials()),
+            Credentials::Profile(profile) => Box::new(profile.credentials()),
+            Credentials::AssumeRole(role) => Box::new(role.credentials()),
+        }
+    }
+}
+
+/// Return the path of an AWS file named by the environment variable `var` when it is set and
+/// not empty, or `~/.aws/<name>` otherwise
+fn aws_file_path(
+    var: &str,
+    name: &str,
+) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => dirs::home_dir().map(|home| home.join(".aws").join(name)),
+    }
+}
+
+/// Look up `key` in the section of an AWS config or credentials file for `profile`, headed
+/// `[profile name]` or `[name]`
+fn profile_setting(
+    contents: &str,
+    profile: &str,
+    key: &str,
+) -> Option<String> {
+    let mut current = None;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let section = line[1..line.len() - 1].trim();
+            current = Some(section.trim_start_matches("profile ").trim());
+            continue;
+        }
+        let mut pair = line.splitn(2, '=').map(str::trim);
+        if let (Some(name), Some(value)) = (pair.next(), pair.next()) {
+            if current == Some(profile) && name == key && !value.is_empty() {
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Look up `key` for `profile` in the AWS config file, at AWS_CONFIG_FILE or `~/.aws/config`,
+/// defaulting to the profile named by AWS_PROFILE or `default`
+fn aws_config_setting(
+    profile: Option<&str>,
+    key: &str,
+) -> Option<String> {
+    let profile = match profile {
+        Some(profile) => profile.to_string(),
+        None => env::var("AWS_PROFILE")
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "default".into()),
+    };
+    let contents = fs::read_to_string(aws_file_path("AWS_CONFIG_FILE", "config")?).ok()?;
+    profile_setting(&contents, &profile, key)
+}
+
+/// Output of a `credential_process` command, version 1 of the format the AWS CLI defines
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessCredentials {
+    version: u8,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<String>,
+}
+
+/// Run a `credential_process` command, split on whitespace, and parse the credentials it prints
+fn process_credentials(command: &str) -> Result<AwsCredentials, CredentialsError> {
+    let mut args = command.split_whitespace();
+    let program = args
+        .next()
+        .ok_or_else(|| CredentialsError::new("credential_process is empty"))?;
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|err| CredentialsError::new(format!("failed to run {}: {}", program, err)))?;
+    if !output.status.success() {
+        return Err(CredentialsError::new(format!(
+            "credential_process failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let parsed = serde_json::from_slice::<ProcessCredentials>(&output.stdout).map_err(|err| {
+        CredentialsError::new(format!("invalid credential_process output: {}", err))
+    })?;
+    if parsed.version != 1 {
+        return Err(CredentialsError::new(format!(
+            "unsupported credential_process output version {}",
+            parsed.version
+        )));
+    }
+    let expires_at = match parsed.expiration {
+        Some(expiration) => Some(
+            DateTime::parse_from_rfc3339(&expiration)
+                .map_err(|err| CredentialsError::new(format!("invalid expiration: {}", err)))?
+                .with_timezone(&Utc),
+        ),
+        None => None,
+    };
+    Ok(AwsCredentials::new(
+        parsed.access_key_id,
+        parsed.secret_access_key,
+        parsed.session_token,
+        expires_at,
+    ))
+}
+
+/// Credentials of a named profile, from its `credential_process` in the AWS config file when
+/// it sets one, or its keys in the shared credentials file otherwise
+///
+/// rusoto's `ProfileProvider` only runs the `credential_process` of AWS_PROFILE or the
+/// default profile, whichever profile it is given, so named profiles are resolved here
+#[derive(Clone, Debug)]
+pub struct NamedProfile {
+    name: String,
+}
+
+impl NamedProfile {
+    fn resolve(&self) -> Result<AwsCredentials, CredentialsError> {
+        if let Some(command) = aws_config_setting(Some(&self.name), "credential_process") {
+            return process_credentials(&command);
+        }
+        let path =
+            aws_file_path("AWS_SHARED_CREDENTIALS_FILE", "credentials").ok_or_else(|| {
+                CredentialsError::new(
+                    "failed to locate the credentials file, set AWS_SHARED_CREDENTIALS_FILE",
+                )
+            })?;
+        let contents = fs::read_to_string(&path).map_err(|err| {
+            CredentialsError::new(format!("failed to read {}: {}", path.display(), err))
+        })?;
+        let setting = |key| profile_setting(&contents, &self.name, key);
+        match (
+            setting("aws_access_key_id"),
+            setting("aws_secret_access_key"),
+        ) {
+            (Some(key), Some(secret)) => Ok(AwsCredentials::new(
+                key,
+                secret,
+                setting("aws_session_token"),
+                None,
+            )),
+            _ => Err(CredentialsError::new(format!(
+                "profile {} not found in {}",
+                self.name,
+                path.display()
+            ))),
+        }
+    }
+}
+
+impl ProvideAwsCredentials for NamedProfile {
+    type Future = future::FutureResult<AwsCredentials, CredentialsError>;
+
+    fn credentials(&self) -> Self::Future {
+        future::result(self.resolve())
+    }
+}
+
+/// Resolve credentials from a named profile, or the default chain otherwise
+///
+/// A named profile's credentials come from its `credential_process` when the AWS config file
+/// sets one, or the shared credentials file otherwise. Other profile settings such as
+/// `role_arn` and `source_profile` aren't followed, so pass `--role-arn` instead.
+/// A credentials file that can't be found or read fails when the credentials are first used.
+///
+/// `timeout` bounds each provider in the default chain, e.g. the instance metadata lookup,
+/// with `None` leaving rusoto's own default in place. The chain is resolved once and cached
+/// until the credentials expire, shared by every client cloned from the result, so scanning
+/// many regions at once doesn't send a burst of requests to the instance metadata service
+pub fn credentials(
+    profile: Option<String>,
+    timeout: Option<Duration>,
+) -> Credentials {
+    match profile {
+        Some(name) => Credentials::Profile(Arc::new(
+            AutoRefreshingProvider::new(NamedProfile { name })
+                .expect("failed to create credentials provider"),
+        )),
+        None => {
+            let mut chain = ChainProvider::new();
+            if let Some(timeout) = timeout {
+                chain.set_timeout(timeout);
+            }
+            Credentials::Chain(Arc::new(
+                AutoRefreshingProvider::new(chain).expect("failed to create credentials provider"),
+            ))
+        }
+    }
+}
+
+/// Return the seconds left as of `now` before `credentials` expire, or `None` for credentials
+/// that never do, e.g. an IAM user's access keys
+pub fn credential_ttl(
+    credentials: &AwsCredentials,
+    now: DateTime<Utc>,
+) -> Option<i64> {
+    credentials
+        .expires_at()
+        .map(|expires_at| (expires_at - now).num_seconds())
+}
+
+/// Parse a human readable size, e.g. `10MB` or `1.5MiB`, into bytes
+///
+/// Decimal and binary suffixes are both treated as powers of 1024,
+/// matching the sizes rendered by `list`
+pub fn parse_size(s: &str) -> Result<i64, String> {
+    let input = s.trim();
+    let pos = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let multiplier: f64 = match input[pos..].trim().to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("invalid size: `{}` has an unknown unit", s)),
+    };
+    let amount = input[..pos]
+        .parse::<f64>()
+        .map_err(|_| format!("invalid size: `{}` is not a number", s))?;
+    Ok((amount * multiplier) as i64)
+}
+
+/// Parse a region name, ignoring surrounding whitespace and case, returning the
+/// unrecognized name on failure
+pub fn parse_region(name: &str) -> Result<Region, String> {
+    Region::from_str(name.trim()).map_err(|_| name.to_owned())
+}
+
+/// Return true for a region marked by `fips_region`
+fn is_fips(region: &Region) -> bool {
+    match region {
+        Region::Custom { endpoint, .. } => endpoint == FIPS_MARKER,
+        _ => false,
+    }
+}
+
+/// Return the FIPS regions in the same partition as `region`, marked by `fips_region`, i.e.
+/// the GovCloud ones for a `us-gov-` region and the commercial ones otherwise, since
+/// credentials for one partition are never valid in the other
+pub fn fips_partition_regions(region: &Region) -> Vec<Region> {
+    let gov = region.name().starts_with("us-gov-");
+    FIPS_REGIONS
+        .iter()
+        .filter(|fips| fips.name().starts_with("us-gov-") == gov)
+        .cloned()
+        .filter_map(|fips| fips_region(fips).ok())
+        .collect()
+}
+
+/// Mark a region so the clients built for it connect to each service's FIPS endpoint, e.g.
+/// `lambda-fips.us-east-1.amazonaws.com`, failing for regions without FIPS endpoints
+pub fn fips_region(region: Region) -> Result<Region, String> {
+    if !FIPS_REGIONS.contains(&region) {
+        return Err(region.name().to_string());
+    }
+    Ok(Region::Custom {
+        name: region.name().to_string(),
+        endpoint: FIPS_MARKER.into(),
+    })
+}
+
+/// Resolve a region marked by `fips_region` to the FIPS endpoint of the given service,
+/// leaving other regions as they are
+fn service_region(
+    region: Region,
+    service: &str,
+) -> Region {
+    match region {
+        Region::Custom { name, endpoint } if endpoint == FIPS_MARKER => Region::Custom {
+            endpoint: format!("https://{}-fips.{}.amazonaws.com", service, name),
+            name,
+        },
+        region => region,
+    }
+}
+
+/// Where the queried region was resolved from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegionSource {
+    Flag,
+    Env(&'static str),
+    Config,
+    Profile,
+    Default,
+}
+
+impl fmt::Display for RegionSource {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self {
+            RegionSource::Flag => write!(f, "--region"),
+            RegionSource::Env(name) => write!(f, "{}", name),
+            RegionSource::Config => write!(f, "config file"),
+            RegionSource::Profile => write!(f, "AWS profile"),
+            RegionSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Resolve the region to query and where it came from, in order from the `--region` flag,
+/// AWS_REGION, AWS_DEFAULT_REGION, the config file and the AWS config file's settings for
+/// `profile`, or AWS_PROFILE or the default profile without one, falling back to us-east-1
+/// when none is set
+///
+/// Unlike `Region::default`, a set but unrecognized name is an error rather than a
+/// silent fallback
+pub fn resolve_region(
+    flag: Option<Region>,
+    config: Option<Region>,
+    profile: Option<&str>,
+) -> Result<(Region, RegionSource), String> {
+    resolve_region_from(
+        flag,
+        config,
+        |name| env::var(name).ok(),
+        || aws_config_setting(profile, "region"),
+    )
+}
+
+fn resolve_region_from(
+    flag: Option<Region>,
+    config: Option<Region>,
+    env: impl Fn(&str) -> Option<String>,
+    profile: impl FnOnce() -> Option<String>,
+) -> Result<(Region, RegionSource), String> {
+    if let Some(region) = flag {
+        return Ok((region, RegionSource::Flag));
+    }
+    for name in &["AWS_REGION", "AWS_DEFAULT_REGION"] {
+        if let Some(value) = env(name) {
+            return parse_region(&value).map(|region| (region, RegionSource::Env(name)));
+        }
+    }
+    if let Some(region) = config {
+        return Ok((region, RegionSource::Config));
+    }
+    match profile() {
+        Some(name) => parse_region(&name).map(|region| (region, RegionSource::Profile)),
+        None => Ok((Region::UsEast1, RegionSource::Default)),
+    }
+}
+
+/// Assume the given role using base credentials, refreshing the session as it expires
+pub fn assume_role(
+    credentials: Credentials,
+    region: Region,
+    role_arn: String,
+    session_name: String,
+) -> Credentials {
+    let sts = StsClient::new_with(
+        HttpClient::new().expect("failed to create request dispatcher"),
+        credentials,
+        region,
+    );
+    let provider = StsAssumeRoleSessionCredentialsProvider::new(
+        sts,
+        role_arn,
+        session_name,
+        None,
+        None,
+        None,
+        None,
+    );
+    Credentials::AssumeRole(Arc::new(
+        AutoRefreshingProvider::new(provider).expect("failed to create assume role provider"),
+    ))
+}
+
+pub fn lambda_client(
+    credentials: Credentials,
+    region: Region,
+) -> LambdaClient {
+    LambdaClient::new_with(
+        HttpClient::new().expect("failed to create request dispatcher"),
+        credentials,
+        service_region(region, "lambda"),
+    )
+}
+
+/// Retry policy for throttled API requests
+#[derive(Debug, Clone)]
+pub struct Retries {
+    /// Number of retries after the first attempt, zero meaning try once
+    pub max: usize,
+    /// Delay before the first retry, growing exponentially after
+    pub delay: Duration,
+    /// Limit on attempts in flight at once, shared by every request
+    pub limiter: Limiter,
+}
+
+/// Caps the number of API requests in flight at once across all of its clones
+#[derive(Debug, Clone, Default)]
+pub struct Limiter {
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl Limiter {
+    /// Allow at most `max` requests in flight, or any number when `None`
+    pub fn new(max: Option<usize>) -> Self {
+        Limiter {
+            semaphore: max.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Defer a request until a slot is free, holding the slot until the request completes
+    pub fn limit<F: Future>(
+        &self,
+        future: F,
+    ) -> Limited<F> {
+        Limited {
+            semaphore: self.semaphore.clone(),
+            permit: Permit::new(),
+            future,
+        }
+    }
+}
+
+/// A request waiting for, or holding, a slot of a `Limiter`
+pub struct Limited<F> {
+    semaphore: Option<Arc<Semaphore>>,
+    permit: Permit,
+    future: F,
+}
+
+impl<F> Limited<F> {
+    fn release(&mut self) {
+        if let Some(semaphore) = &self.semaphore {
+            self.permit.release(semaphore)
+        }
+    }
+}
+
+impl<F: Future> Future for Limited<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        if let Some(semaphore) = &self.semaphore {
+            let acquired = self
+                .permit
+                .poll_acquire(semaphore)
+                .expect("limiter semaphore is never closed");
+            if acquired.is_not_ready() {
+                return Ok(Async::NotReady);
+            }
+        }
+        let result = self.future.poll();
+        match result {
+            Ok(Async::NotReady) => (),
+            _ => self.release(),
+        }
+        result
+    }
+}
+
+impl<F> Drop for Limited<F> {
+    fn drop(&mut self) {
+        self.release()
+    }
+}
+
+fn backoff(retries: &Retries) -> Strategy {
+    Strategy::exponential(retries.delay)
+        .with_max_retries(retries.max)
+        .with_jitter(true)
+}
+
+pub fn tags_client(
+    credentials: Credentials,
+    region: Region,
+) -> ResourceGroupsTaggingApiClient {
+    ResourceGroupsTaggingApiClient::new_with(
+        HttpClient::new().expect("failed to create request dispatcher"),
+        credentials,
+        service_region(region, "tagging"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        code_storage, credential_ttl, credentials, delete_version, estimated_storage_cost, filters,
+        find_stale_layers, fips_partition_regions, fips_region, for_each_func_concurrent, future,
+        group_funcs, group_funcs_by_tag, human_total_size, is_fips, join, lambdas_retryable,
+        layer_version, markdown_cell, paginate, percentages, profile_setting, prometheus_label,
+        purgeable_versions, region_clients, render_aliases, render_cost, render_diff, render_funcs,
+        render_go_migration, render_grouped, render_near_limit, render_outliers, render_problems,
+        render_purge, render_runtimes, render_storage, render_summary, render_tag_values,
+        render_tags, render_template, resolve_region_from, runtime_counts, service_region,
+        sort_funcs, stream, tag_names, tags_retryable, truncate, unique_lambdas, vpc_summary,
+        write_snapshot, Arc, AtomicUsize, AwsCredentials, Column, Duration, Error, Fixture, Func,
+        FuncDiff, FunctionConfiguration, GetResourcesError, Group, GroupBy, LambdaClient, Limiter,
+        ListFunctionsError, NaiveDate, Ordering, Output, Path, Progress, Record, Regex, Region,
+        RegionSource, ResourceTagMapping, Retries, RuntimeFamily, RusotoError, Select, Snapshot,
+        Sort, Source, StaleLayer, Stats, Tag, TagFilter, Template, TextLayout, Tracing, Units,
+        Versions, NEAR_LIMIT_PCT, OUTLIER_MEMORY_MB, OUTLIER_TIMEOUT_SECS, STORAGE_PRICE_PER_GB,
+    };
+    use chrono::{DateTime, Utc};
+    use futures::{
+        executor::{self, Notify, NotifyHandle},
+        Async, Future, Stream,
+    };
+    use rusoto_lambda::{
+        AliasConfiguration, AliasRoutingConfiguration, DeadLetterConfig, EnvironmentResponse,
+        Layer, TracingConfigResponse, VpcConfigResponse,
+    };
+    use std::collections::BTreeSet;
+    #[test]
+    fn func_human_size() {
+        let func = |code_size| Func {
+            config: FunctionConfiguration {
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!("1 KB", func(1024).human_size(Units::Conventional));
+        assert_eq!("1 KiB", func(1024).human_size(Units::Binary));
+        assert_eq!("1 KB", func(1000).human_size(Units::Decimal))
+    }
+    #[test]
+    fn func_human_memory_and_timeout() {
+        let func = Func {
+            config: FunctionConfiguration {
+                memory_size: Some(128),
+                timeout: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!("128 MB", func.human_memory());
+        assert_eq!("3s", func.human_timeout());
+        assert_eq!("-", Func::default().human_memory());
+        assert_eq!("-", Func::default().human_timeout())
+    }
+    #[test]
+    fn column_values() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("foo".into()),
+                ..Default::default()
+            },
+            tags: vec![
+                Tag {
+                    key: "team".into(),
+                    value: "foo".into(),
+                },
+                Tag {
+                    key: "env".into(),
+                    value: "prod".into(),
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!("foo", Column::Name.value(&func, Units::Conventional));
+        assert_eq!("-", Column::Runtime.value(&func, Units::Conventional));
+        assert_eq!("-", Column::Handler.value(&func, Units::Conventional));
+        assert_eq!("-", Column::Arn.value(&func, Units::Conventional));
+        assert_eq!(
+            "env=prod,team=foo",
+            Column::Tags.value(&func, Units::Conventional)
+        );
+        assert_eq!(
+            "-",
+            Column::Tags.value(&Func::default(), Units::Conventional)
+        );
+        assert_eq!("2", Column::TagCount.value(&func, Units::Conventional));
+        assert_eq!(
+            "0",
+            Column::TagCount.value(&Func::default(), Units::Conventional)
+        );
+        assert_eq!(
+            "none",
+            Column::Dlq.value(&Func::default(), Units::Conventional)
+        );
+        let func = Func {
+            config: FunctionConfiguration {
+                dead_letter_config: Some(DeadLetterConfig {
+                    target_arn: Some("arn:aws:sqs:us-east-1:123456789012:dlq".into()),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            "arn:aws:sqs:us-east-1:123456789012:dlq",
+            Column::Dlq.value(&func, Units::Conventional)
+        );
+        assert_eq!(
+            "unreserved",
+            Column::Concurrency.value(&func, Units::Conventional)
+        );
+        let func = Func {
+            reserved_concurrency: Some(50),
+            ..Default::default()
+        };
+        assert_eq!("50", Column::Concurrency.value(&func, Units::Conventional))
+    }
+    #[test]
+    fn render_funcs_without_runtime() {
+        let funcs = vec![Func {
+            config: FunctionConfiguration {
+                function_name: Some("image".into()),
+                runtime: None,
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+        let units = Units::Conventional;
+        assert_eq!("-", Column::Runtime.value(&funcs[0], units));
+        render_funcs(
+            &mut Vec::new(),
+            &funcs,
+            Output::Text,
+            &[],
+            units,
+            TextLayout::default(),
+        );
+        render_funcs(
+            &mut Vec::new(),
+            &funcs,
+            Output::Csv,
+            &[],
+            units,
+            TextLayout::default(),
+        )
+    }
+    #[test]
+    fn render_funcs_to_writer() {
+        let funcs = vec![Func {
+            config: FunctionConfiguration {
+                function_name: Some("foo".into()),
+                runtime: Some("python3.7".into()),
+                code_size: Some(1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+        let units = Units::Conventional;
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Csv,
+            &[Column::Name, Column::Runtime, Column::Size],
+            units,
+            TextLayout::default(),
+        );
+        assert_eq!(
+            "name,runtime,size\nfoo,python3.7,1 KB\n",
+            String::from_utf8(out).unwrap()
+        );
+        let without_header = TextLayout {
+            header: false,
+            ..TextLayout::default()
+        };
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Csv,
+            &[Column::Name, Column::Runtime],
+            units,
+            without_header,
+        );
+        assert_eq!("foo,python3.7\n", String::from_utf8(out).unwrap());
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Tsv,
+            &[Column::Name, Column::Runtime, Column::Size],
+            units,
+            TextLayout::default(),
+        );
+        assert_eq!(
+            "name\truntime\tsize\nfoo\tpython3.7\t1 KB\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Text,
+            &[Column::Name, Column::Size],
+            units,
+            without_header,
+        );
+        assert_eq!("foo  1 KB\n", String::from_utf8(out).unwrap());
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Text,
+            &[],
+            units,
+            TextLayout::default(),
+        );
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("NAME"));
+        assert!(text.contains("foo"));
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Text,
+            &[Column::Name, Column::Size],
+            units,
+            TextLayout {
+                padding: 4,
+                min_width: 8,
+                ..TextLayout::default()
+            },
+        );
+        assert_eq!(
+            "NAME        SIZE\nfoo         1 KB\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Markdown,
+            &[Column::Name, Column::Size],
+            units,
+            TextLayout::default(),
+        );
+        assert_eq!(
+            "| NAME | SIZE |\n| --- | ---: |\n| foo | 1 KB |\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Markdown,
+            &[Column::Name, Column::Size],
+            units,
+            without_header,
+        );
+        assert_eq!("| foo | 1 KB |\n", String::from_utf8(out).unwrap());
+        assert_eq!("a=1\\|2", markdown_cell("a=1|2"));
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Jsonl,
+            &[],
+            units,
+            TextLayout::default(),
+        );
+        let lines = String::from_utf8(out).unwrap();
+        assert_eq!(1, lines.lines().count());
+        let record: serde_json::Value = serde_json::from_str(lines.trim_end()).unwrap();
+        assert_eq!("foo", record["name"]);
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Table,
+            &[Column::Name, Column::Size],
+            units,
+            TextLayout::default(),
+        );
+        assert_eq!(
+            "┌──────┬──────┐\n│ NAME │ SIZE │\n├──────┼──────┤\n│ foo  │ 1 KB │\n└──────┴──────┘\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Table,
+            &[Column::Name, Column::Size],
+            units,
+            without_header,
+        );
+        assert_eq!(
+            "┌─────┬──────┐\n│ foo │ 1 KB │\n└─────┴──────┘\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_funcs(
+            &mut out,
+            &funcs,
+            Output::Prometheus,
+            &[],
+            units,
+            TextLayout::default(),
+        );
+        let metrics = String::from_utf8(out).unwrap();
+        assert!(metrics.contains("# TYPE lambda_code_size_bytes gauge\n"));
+        assert!(metrics
+            .contains("lambda_code_size_bytes{function=\"foo\",runtime=\"python3.7\"} 1024\n"));
+        assert!(!metrics.contains("lambda_timeout_seconds{"));
+        assert_eq!("a\\\"b\\\\c\\n", prometheus_label("a\"b\\c\n"));
+        let mut out = Vec::new();
+        render_summary(&mut out, &funcs, units);
+        assert_eq!("1 functions, 1 KB total\n", String::from_utf8(out).unwrap())
+    }
+    #[test]
+    fn storage_cost_estimate() {
+        let func = |code_size: i64| Func {
+            config: FunctionConfiguration {
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let funcs = vec![
+            func(3 * 1024 * 1024 * 1024),
+            func(1024 * 1024 * 1024),
+            Func::default(),
+        ];
+        assert_eq!(0.4, estimated_storage_cost(&funcs, STORAGE_PRICE_PER_GB));
+        let mut out = Vec::new();
+        render_cost(&mut out, &funcs, 0.25);
+        assert_eq!(
+            "estimated monthly storage cost: $1.00 at $0.25 per GB\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn render_aliases_to_writer() {
+        let func = |name: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let alias = |name: &str, version: &str| AliasConfiguration {
+            name: Some(name.into()),
+            function_version: Some(version.into()),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        render_aliases(
+            &mut out,
+            &[
+                (
+                    func("checkout"),
+                    vec![alias("live", "3"), alias("canary", "4")],
+                ),
+                (func("refund"), vec![]),
+            ],
+        );
+        assert_eq!(
+            "NAME      ALIASES\ncheckout  canary=4,live=3\nrefund    -\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn versions_exclude_latest() {
+        let version = |version: &str, code_size: i64| FunctionConfiguration {
+            version: Some(version.into()),
+            code_size: Some(code_size),
+            ..Default::default()
+        };
+        assert_eq!(
+            Versions {
+                count: 2,
+                code_size: 3072
+            },
+            Versions::from(
+                &[
+                    version("$LATEST", 1024),
+                    version("1", 1024),
+                    version("2", 2048)
+                ][..]
+            )
+        );
+        assert_eq!(Versions::default(), Versions::from(&[][..]))
+    }
+    #[test]
+    fn purgeable_versions_spare_newest_and_aliased() {
+        let version = |version: &str| FunctionConfiguration {
+            version: Some(version.into()),
+            code_size: Some(1024),
+            ..Default::default()
+        };
+        let versions = ["$LATEST", "1", "2", "3", "4", "5", "10", "11"]
+            .iter()
+            .map(|v| version(v))
+            .collect::<Vec<_>>();
+        let aliases = vec![
+            AliasConfiguration {
+                function_version: Some("2".into()),
+                ..Default::default()
+            },
+            AliasConfiguration {
+                function_version: Some("11".into()),
+                routing_config: Some(AliasRoutingConfiguration {
+                    additional_version_weights: Some(
+                        vec![("4".to_string(), 0.1)].into_iter().collect(),
+                    ),
+                }),
+                ..Default::default()
+            },
+        ];
+        let purgeable = purgeable_versions(&versions, &aliases, 1);
+        assert_eq!(
+            vec!["5", "3", "1"],
+            purgeable
+                .iter()
+                .filter_map(|version| version.version.as_deref())
+                .collect::<Vec<_>>()
+        );
+        assert!(purgeable_versions(&versions[..1], &[], 0).is_empty());
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("checkout".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let purges = vec![(func, purgeable)];
+        let mut out = Vec::new();
+        render_purge(&mut out, &purges, None, Units::Conventional);
+        assert_eq!(
+            "NAME      VERSION  SIZE\ncheckout  5        1 KB\ncheckout  3        1 KB\ncheckout  1        1 KB\nwould delete 3 versions, freeing 3 KB, pass --apply to delete them\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_purge(&mut out, &purges, Some(1), Units::Conventional);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("deleted 2 of 3 versions, 1 failed\n"))
+    }
+    #[test]
+    fn purgeable_versions_keep_on_top_of_aliased() {
+        let versions = ["5", "4", "3", "2", "1"]
+            .iter()
+            .map(|version| FunctionConfiguration {
+                version: Some(version.to_string()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let aliases = vec![AliasConfiguration {
+            function_version: Some("5".into()),
+            ..Default::default()
+        }];
+        assert_eq!(
+            vec!["2", "1"],
+            purgeable_versions(&versions, &aliases, 2)
+                .iter()
+                .filter_map(|version| version.version.as_deref())
+                .collect::<Vec<_>>()
+        )
+    }
+    #[test]
+    fn delete_version_requires_version_number() {
+        let retries = Retries {
+            max: 0,
+            delay: Duration::from_millis(0),
+            limiter: Limiter::default(),
+        };
+        for version in &["", "$LATEST", "live"] {
+            let deleted = delete_version(
+                LambdaClient::new(Region::UsEast1),
+                "checkout".into(),
+                version.to_string(),
+                retries.clone(),
+            )
+            .wait();
+            assert!(match deleted {
+                Err(Error::UnsafeDelete(_, _)) => true,
+                _ => false,
+            })
+        }
+    }
+    #[test]
+    fn render_storage_against_quota() {
+        let func = Func {
+            config: FunctionConfiguration {
+                code_size: Some(1024 * 1024 * 1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let versions = Versions {
+            count: 2,
+            code_size: 2 * 1024 * 1024 * 1024,
+        };
+        let funcs = vec![(func, versions), (Func::default(), Versions::default())];
+        assert_eq!(3 * 1024 * 1024 * 1024, code_storage(&funcs));
+        let mut out = Vec::new();
+        render_storage(&mut out, &funcs, 75.0, Units::Binary);
+        assert_eq!(
+            "code storage: 3 GiB of 75 GB (4.0%)\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn stale_layers_found_against_latest() {
+        let func = |name: &str, layers: &[&str]| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                layers: Some(
+                    layers
+                        .iter()
+                        .map(|arn| Layer {
+                            arn: Some(arn.to_string()),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let layer = "arn:aws:lambda:us-east-1:123456789012:layer:deps";
+        let other = "arn:aws:lambda:us-east-1:123456789012:layer:other";
+        let funcs = vec![
+            func("current", &[&format!("{}:4", layer)]),
+            func(
+                "behind",
+                &[&format!("{}:2", layer), &format!("{}:1", other)],
+            ),
+        ];
+        let latest = vec![(layer.to_string(), 4)].into_iter().collect();
+        assert_eq!(
+            vec![StaleLayer {
+                name: "behind".into(),
+                layer: layer.into(),
+                version: 2,
+                latest: 4,
+            }],
+            find_stale_layers(&funcs, &latest)
+        );
+        assert_eq!(Some((layer, 4)), layer_version(&format!("{}:4", layer)));
+        assert_eq!(
+            None,
+            layer_version("arn:aws:lambda:us-east-1:123456789012:layer:deps")
+        )
+    }
+    #[test]
+    fn render_tags_to_writer() {
+        let mut tags = BTreeSet::new();
+        tags.insert(("team".to_string(), None));
+        tags.insert(("env".to_string(), Some("prod".to_string())));
+        let mut out = Vec::new();
+        render_tags(&mut out, tags);
+        assert_eq!("env=prod\nteam\n", String::from_utf8(out).unwrap())
+    }
+    #[test]
+    fn runtimes_by_count() {
+        let func = |runtime: Option<&str>| Func {
+            config: FunctionConfiguration {
+                runtime: runtime.map(String::from),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let funcs = vec![
+            func(Some("nodejs18.x")),
+            func(None),
+            func(Some("python3.12")),
+            func(Some("python3.12")),
+        ];
+        let mut out = Vec::new();
+        render_runtimes(&mut out, runtime_counts(&funcs));
+        assert_eq!(
+            "RUNTIME     FUNCTIONS\npython3.12  2\nimage       1\nnodejs18.x  1\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn render_tag_values_in_columns() {
+        let tags = vec![("team", "payments"), ("env", "staging"), ("env", "prod")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let mut out = Vec::new();
+        render_tag_values(&mut out, tags);
+        assert_eq!(
+            "KEY   VALUE\nenv   prod\n      staging\nteam  payments\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn diff_funcs_by_arn() {
+        let func = |name: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                function_arn: Some(format!("arn:{}", name)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let diff = FuncDiff::new(vec![func("a"), func("b")], vec![func("b"), func("c")]);
+        let mut out = Vec::new();
+        render_diff(&mut out, &diff, "team=a", "team=b");
+        assert_eq!(
+            "only in team=a (1)\n  a\nin both (1)\n  b\nonly in team=b (1)\n  c\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn truncate_with_ellipsis() {
+        assert_eq!("env=prod", truncate("env=prod", 8));
+        assert_eq!("env=p…", truncate("env=prod", 6));
+        assert_eq!("…", truncate("env=prod", 1))
+    }
+    #[test]
+    fn func_record_yaml() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("foo".into()),
+                code_size: Some(1024),
+                ..Default::default()
+            },
+            tags: vec![Tag {
+                key: "team".into(),
+                value: "foo".into(),
+            }],
+            ..Default::default()
+        };
+        let yaml = serde_yaml::to_string(&vec![Record::new(&func, Units::Conventional)]).unwrap();
+        assert!(yaml.contains("name: foo"));
+        assert!(yaml.contains("human_size: 1 KB"));
+        assert!(yaml.contains("team: foo"));
+        assert!(serde_yaml::to_string(&Vec::<Record>::new())
+            .unwrap()
+            .contains("[]"))
+    }
+    #[test]
+    fn funcs_grouped_by_runtime() {
+        let func = |runtime: Option<&str>, code_size| Func {
+            config: FunctionConfiguration {
+                runtime: runtime.map(String::from),
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let groups = group_funcs(
+            &[
+                func(Some("python3.7"), 10),
+                func(None, 5),
+                func(Some("nodejs10.x"), 1),
+                func(Some("python3.7"), 20),
+            ],
+            GroupBy::Runtime,
+        );
+        assert_eq!(
+            vec![None, Some("nodejs10.x".into()), Some("python3.7".into())],
+            groups.keys().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(&Group {
+                count: 2,
+                code_size: 30
+            }),
+            groups.get(&Some("python3.7".into()))
+        )
+    }
+    #[test]
+    fn funcs_grouped_by_tag() {
+        let func = |team: Option<&str>, code_size| Func {
+            config: FunctionConfiguration {
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            tags: team
+                .into_iter()
+                .map(|team| Tag {
+                    key: "team".into(),
+                    value: team.into(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let groups = group_funcs_by_tag(
+            &[
+                func(Some("payments"), 1024),
+                func(None, 512),
+                func(Some("payments"), 2048),
+            ],
+            "team",
+        );
+        let mut out = Vec::new();
+        render_grouped(&mut out, &groups, "team", "untagged", Units::Conventional);
+        assert_eq!(
+            "TEAM      COUNT  SIZE     AVERAGE  SHARE\nuntagged  1      512 B    512 B    33%\npayments  2      3 KB     1.50 KB  67%\ntotal     3      3.50 KB  1.17 KB  100%\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn percentages_sum_to_100() {
+        assert_eq!(vec![34, 33, 33], percentages(&[1, 1, 1]));
+        assert_eq!(vec![67, 33], percentages(&[2, 1]));
+        assert_eq!(vec![100], percentages(&[5]));
+        assert_eq!(vec![0, 0], percentages(&[0, 0]));
+        assert_eq!(100, percentages(&[7, 5, 3, 1, 1]).iter().sum::<usize>())
+    }
+    #[test]
+    fn funcs_stats() {
+        let func = |code_size, tagged| Func {
+            config: FunctionConfiguration {
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            tags: if tagged {
+                vec![Tag {
+                    key: "team".into(),
+                    value: "foo".into(),
+                }]
+            } else {
+                vec![]
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            Stats {
+                count: 4,
+                total_size: 100,
+                average_size: 25,
+                median_size: 15,
+                untagged: 1,
+            },
+            Stats::from(
+                &[
+                    func(10, true),
+                    func(70, false),
+                    func(20, true),
+                    func(0, true)
+                ][..]
+            )
+        );
+        assert_eq!(Stats::default(), Stats::from(&[][..]))
+    }
+    #[test]
+    fn funcs_sorted_in_reverse() {
+        let func = |name: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut funcs = vec![func("b"), func("a"), func("c")];
+        sort_funcs(&mut funcs, Sort::Name, true, false, false);
+        assert_eq!(
+            vec![Some("c".into()), Some("b".into()), Some("a".into())],
+            funcs.iter().map(Func::name).collect::<Vec<_>>()
+        )
+    }
+    #[test]
+    fn funcs_sorted_by_name_within_ties() {
+        let func = |name: &str, runtime: &str, region: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                function_arn: Some(format!(
+                    "arn:aws:lambda:{}:123456789012:function:{}",
+                    region, name
+                )),
+                runtime: Some(runtime.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut funcs = vec![
+            func("b", "python3.7", "us-east-1"),
+            func("c", "nodejs10.x", "us-east-1"),
+            func("a", "python3.7", "us-west-2"),
+            func("a", "python3.7", "eu-west-1"),
+        ];
+        sort_funcs(&mut funcs, Sort::Runtime, true, false, false);
+        assert_eq!(
+            vec!["a eu-west-1", "a us-west-2", "b us-east-1", "c us-east-1"],
+            funcs
+                .iter()
+                .map(|func| format!(
+                    "{} {}",
+                    func.name().unwrap_or_default(),
+                    func.region()
+                        .map(|region| region.name().to_string())
+                        .unwrap_or_default()
+                ))
+                .collect::<Vec<_>>()
+        )
+    }
+    #[test]
+    fn funcs_sorted_naturally() {
+        let func = |name: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let names = |funcs: &[Func]| funcs.iter().map(Func::name).collect::<Vec<_>>();
+        let mut funcs = vec![func("worker-10"), func("worker-2"), func("worker-1")];
+        sort_funcs(&mut funcs, Sort::Name, false, false, false);
+        assert_eq!(
+            vec![
+                Some("worker-1".into()),
+                Some("worker-10".into()),
+                Some("worker-2".into())
+            ],
+            names(&funcs)
+        );
+        sort_funcs(&mut funcs, Sort::Name, false, true, false);
+        assert_eq!(
+            vec![
+                Some("worker-1".into()),
+                Some("worker-2".into()),
+                Some("worker-10".into())
+            ],
+            names(&funcs)
+        )
+    }
+    #[test]
+    fn funcs_sorted_ignoring_case() {
+        let func = |name: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let names = |funcs: &[Func]| funcs.iter().map(Func::name).collect::<Vec<_>>();
+        let mut funcs = vec![func("bar"), func("foo"), func("Foo"), func("Bar")];
+        sort_funcs(&mut funcs, Sort::Name, false, false, false);
+        assert_eq!(
+            vec![
+                Some("Bar".into()),
+                Some("Foo".into()),
+                Some("bar".into()),
+                Some("foo".into())
+            ],
+            names(&funcs)
+        );
+        sort_funcs(&mut funcs, Sort::Name, false, false, true);
+        assert_eq!(
+            vec![
+                Some("Bar".into()),
+                Some("bar".into()),
+                Some("Foo".into()),
+                Some("foo".into())
+            ],
+            names(&funcs)
+        );
+        sort_funcs(&mut funcs, Sort::Name, true, false, true);
+        assert_eq!(
+            vec![
+                Some("foo".into()),
+                Some("Foo".into()),
+                Some("bar".into()),
+                Some("Bar".into())
+            ],
+            names(&funcs)
+        )
+    }
+    #[test]
+    fn funcs_human_total_size() {
+        let func = |code_size| Func {
+            config: FunctionConfiguration {
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            "2 KB",
+            human_total_size(&[func(512), func(1536)], Units::Conventional)
+        )
+    }
+    #[test]
+    fn func_last_modified() {
+        assert_eq!(
+            DateTime::parse_from_rfc3339("2019-06-20T17:31:15.964+00:00").ok(),
+            Func {
+                config: FunctionConfiguration {
+                    last_modified: Some("2019-06-20T17:31:15.964+0000".into()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .last_modified()
+        )
+    }
+    #[test]
+    fn func_human_age() {
+        let func = Func {
+            config: FunctionConfiguration {
+                last_modified: Some("2019-06-20T17:31:15.964+0000".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        assert_eq!("44s ago", func.human_age(at("2019-06-20T17:32:00+00:00")));
+        assert_eq!("3d ago", func.human_age(at("2019-06-23T18:00:00+00:00")));
+        assert_eq!("2mo ago", func.human_age(at("2019-08-20T18:00:00+00:00")));
+        assert_eq!("1y ago", func.human_age(at("2020-08-01T00:00:00+00:00")));
+        assert_eq!(
+            "-",
+            Func::default().human_age(at("2019-06-20T17:32:00+00:00"))
+        )
+    }
+    #[test]
+    fn credential_ttl_until_expiry() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let now = at("2019-06-20T17:00:00+00:00");
+        let expiring = |expires_at| AwsCredentials::new("key", "secret", None, expires_at);
+        assert_eq!(None, credential_ttl(&expiring(None), now));
+        assert_eq!(
+            Some(600),
+            credential_ttl(&expiring(Some(at("2019-06-20T17:10:00+00:00"))), now)
+        );
+        assert_eq!(
+            Some(-60),
+            credential_ttl(&expiring(Some(at("2019-06-20T16:59:00+00:00"))), now)
+        )
+    }
+    #[test]
+    fn func_env_vars() {
+        assert!(Func::default().env_vars().is_empty());
+        let func = Func {
+            config: FunctionConfiguration {
+                environment: Some(EnvironmentResponse {
+                    variables: Some(
+                        vec![("B".to_string(), "2".to_string()), ("A".into(), "1".into())]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["A", "B"],
+            func.env_vars()
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+        );
+        assert!(func.has_env_vars());
+        assert!(!Func::default().has_env_vars())
+    }
+    #[test]
+    fn func_is_deprecated_runtime() {
+        let func = |runtime: &str| Func {
+            config: FunctionConfiguration {
+                runtime: Some(runtime.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func("python2.7").is_deprecated_runtime());
+        assert!(!func("python3.12").is_deprecated_runtime());
+        assert!(!Func::default().is_deprecated_runtime());
+        assert!(func("go1.x").is_deprecated_runtime());
+        assert!(func("go1.x").runtime_suggestion().is_some());
+        assert_eq!(None, func("python2.7").runtime_suggestion())
+    }
+    #[test]
+    fn render_go_migration_lists_go_functions() {
+        let func = |name: &str, runtime: &str| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                runtime: Some(runtime.into()),
+                handler: Some("main".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        render_go_migration(
+            &mut out,
+            &[func("api", "go1.x"), func("worker", "python3.12")],
+        );
+        assert_eq!(
+            "NAME  HANDLER  MIGRATE TO\napi   main     provided.al2023 or provided.al2, built as a bootstrap executable\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_go_migration(&mut out, &[func("worker", "python3.12")]);
+        assert!(out.is_empty())
+    }
+    #[test]
+    fn select_expressions() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("api".into()),
+                runtime: Some("nodejs18.x".into()),
+                code_size: Some(20 * 1024 * 1024),
+                memory_size: Some(512),
+                ..Default::default()
+            },
+            tags: vec![Tag {
+                key: "team".into(),
+                value: "payments".into(),
+            }],
+            ..Default::default()
+        };
+        let matches = |s: &str| s.parse::<Select>().unwrap().matches(&func);
+        assert!(matches(
+            r#"runtime ~ "nodejs" && size > 10MB && tag:team == "payments""#
+        ));
+        assert!(matches("memory < 1024 && (name == other || name == api)"));
+        assert!(matches("tag:env != prod && timeout != 30"));
+        assert!(!matches(
+            "size > 10MB && runtime == python3.12 || memory > 512"
+        ));
+        assert!(!matches("tag:env == prod"));
+        let error = |s: &str| s.parse::<Select>().unwrap_err();
+        assert!(error("runtime = node").starts_with("at column 9:"));
+        assert!(error("colour == red").starts_with("at column 1: unknown field colour"));
+        assert_eq!(
+            "at column 8: memory is a number, compare it with ==, !=, > or <",
+            error("memory ~ 512")
+        );
+        assert_eq!(
+            "at column 8: invalid size: `big` has an unknown unit",
+            error("size > big")
+        );
+        assert_eq!("at column 11: expected )", error("(name == a"));
+        assert_eq!("at column 11: expected && or ||", error("name == a b"));
+        assert_eq!("at column 9: unclosed string", error(r#"name == "a"#));
+        assert_eq!("at column 8: expected a value", error("name =="))
+    }
+    #[test]
+    fn render_outliers_with_reason() {
+        let func = |name: &str, timeout, memory_size| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                timeout: Some(timeout),
+                memory_size: Some(memory_size),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let funcs = [
+            func("batch", 900, 3008),
+            func("api", 30, 3008),
+            func("cron", 900, 128),
+        ];
+        assert_eq!(
+            None,
+            funcs[2].outlier_reason(OUTLIER_TIMEOUT_SECS, OUTLIER_MEMORY_MB)
+        );
+        assert!(funcs[2].outlier_reason(300, 64).is_some());
+        let mut out = Vec::new();
+        render_outliers(&mut out, &funcs, OUTLIER_TIMEOUT_SECS, OUTLIER_MEMORY_MB);
+        assert_eq!(
+            "NAME   REASON\nbatch  timeout 900s over 60s and memory 3008 MB over 1024 MB\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_outliers(
+            &mut out,
+            &funcs[1..],
+            OUTLIER_TIMEOUT_SECS,
+            OUTLIER_MEMORY_MB,
+        );
+        assert!(out.is_empty())
+    }
+    #[test]
+    fn render_near_limit_shows_headroom() {
+        let func = |name: &str, code_size| Func {
+            config: FunctionConfiguration {
+                function_name: Some(name.into()),
+                code_size: Some(code_size),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mb = 1024 * 1024;
+        let funcs = [
+            func("small", 10 * mb),
+            func("near", 48 * mb),
+            func("over", 51 * mb),
+        ];
+        assert_eq!(None, funcs[0].upload_headroom(NEAR_LIMIT_PCT));
+        assert_eq!(Some(2 * mb), funcs[1].upload_headroom(NEAR_LIMIT_PCT));
+        assert_eq!(Some(10 * mb), func("wide", 40 * mb).upload_headroom(20.0));
+        let mut out = Vec::new();
+        render_near_limit(&mut out, &funcs, NEAR_LIMIT_PCT, Units::Conventional);
+        assert_eq!(
+            "NAME  SIZE   HEADROOM\nnear  48 MB  2 MB\nover  51 MB  over by 1 MB\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        render_near_limit(&mut out, &funcs[..1], NEAR_LIMIT_PCT, Units::Conventional);
+        assert!(out.is_empty())
+    }
+    #[test]
+    fn func_matches_name() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("prod-billing-worker".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func.matches_name(None));
+        assert!(func.matches_name(Regex::new("^prod-.*-worker$").ok().as_ref()));
+        assert!(!func.matches_name(Regex::new("^dev-").ok().as_ref()))
+    }
+    #[test]
+    fn join_drops_unmatched() {
+        let funcs = join(
+            vec![
+                ResourceTagMapping {
+                    resource_arn: Some("arn:foo".into()),
+                    tags: Some(vec![Tag {
+                        key: "team".into(),
+                        value: "foo".into(),
+                    }]),
+                },
+                ResourceTagMapping {
+                    resource_arn: Some("arn:deleted".into()),
+                    ..Default::default()
+                },
+            ],
+            vec![
+                FunctionConfiguration {
+                    function_arn: Some("arn:foo".into()),
+                    function_name: Some("foo".into()),
+                    ..Default::default()
+                },
+                FunctionConfiguration {
+                    function_arn: Some("arn:untagged".into()),
+                    ..Default::default()
+                },
+            ],
+            false,
+        );
+        assert_eq!(1, funcs.len());
+        assert_eq!(Some("foo".into()), funcs[0].name());
+        assert_eq!(1, funcs[0].tags.len())
+    }
+    #[test]
+    fn join_keeps_untagged() {
+        let funcs = join(
+            vec![ResourceTagMapping {
+                resource_arn: Some("arn:deleted".into()),
+                ..Default::default()
+            }],
+            vec![FunctionConfiguration {
+                function_arn: Some("arn:untagged".into()),
+                function_name: Some("untagged".into()),
+                ..Default::default()
+            }],
+            true,
+        );
+        assert_eq!(1, funcs.len());
+        assert_eq!(Some("untagged".into()), funcs[0].name());
+        assert!(funcs[0].tags.is_empty())
+    }
+    #[test]
+    fn func_has_tag() {
+        let func = Func {
+            tags: vec![Tag {
+                key: "env".into(),
+                value: "sandbox".into(),
+            }],
+            ..Default::default()
+        };
+        assert!(func.has_tag("env", "sandbox"));
+        assert!(!func.has_tag("env", "prod"));
+        assert!(!func.has_tag("team", "sandbox"));
+        assert!(func.has_tag_key("env"));
+        assert!(!func.has_tag_key("team"))
+    }
+    #[test]
+    fn func_missing_tags() {
+        let func = Func {
+            tags: vec![Tag {
+                key: "team".into(),
+                value: "payments".into(),
+            }],
+            ..Default::default()
+        };
+        let required = vec!["owner".to_string(), "team".into(), "cost-center".into()];
+        assert_eq!(vec!["owner", "cost-center"], func.missing_tags(&required));
+        assert!(func.missing_tags(&["team".into()]).is_empty())
+    }
+    #[test]
+    fn func_modified_between() {
+        let func = Func {
+            config: FunctionConfiguration {
+                last_modified: Some("2019-06-28T17:12:53.262+0000".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let date = |s: &str| s.parse::<NaiveDate>().ok();
+        assert!(func.modified_between(None, None));
+        assert!(func.modified_between(date("2019-06-28"), date("2019-06-29")));
+        assert!(!func.modified_between(None, date("2019-06-28")));
+        assert!(!func.modified_between(date("2019-06-29"), None));
+        assert!(!Func::default().modified_between(None, date("2019-06-28")))
+    }
+    #[test]
+    fn func_vpc_summary() {
+        let func = |vpc_id: &str| Func {
+            config: FunctionConfiguration {
+                vpc_config: Some(VpcConfigResponse {
+                    vpc_id: Some(vpc_id.into()),
+                    subnet_ids: Some(vec!["subnet-a".into(), "subnet-b".into()]),
+                    security_group_ids: Some(vec!["sg-a".into()]),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            ("vpc-123".into(), "2".into(), "1".into()),
+            vpc_summary(&func("vpc-123"))
+        );
+        assert_eq!(("-".into(), "-".into(), "-".into()), vpc_summary(&func("")));
+        assert_eq!(
+            ("-".into(), "-".into(), "-".into()),
+            vpc_summary(&Func::default())
+        )
+    }
+    #[test]
+    fn func_matches_sha256() {
+        let func = Func {
+            config: FunctionConfiguration {
+                code_sha_256: Some("abc123=".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func.matches_sha256(None));
+        assert!(func.matches_sha256(Some("abc123=")));
+        assert!(!func.matches_sha256(Some("def456=")));
+        assert!(!Func::default().matches_sha256(Some("abc123=")));
+        assert_eq!("abc123=", Column::Sha256.value(&func, Units::Conventional))
+    }
+    #[test]
+    fn func_matches_description() {
+        let func = |description: &str| Func {
+            config: FunctionConfiguration {
+                description: Some(description.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func("Resizes Uploaded Images").matches_description(None));
+        assert!(func("Resizes Uploaded Images").matches_description(Some("uploaded")));
+        assert!(!func("Resizes Uploaded Images").matches_description(Some("thumbnail")));
+        assert!(!func("").matches_description(Some("")));
+        assert!(!Func::default().matches_description(Some("uploaded")));
+        assert_eq!(
+            "-",
+            Column::Description.value(&func(""), Units::Conventional)
+        )
+    }
+    #[test]
+    fn func_region_from_arn() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_arn: Some("arn:aws:lambda:eu-west-1:123456789012:function:foo".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(Some(Region::EuWest1), func.region());
+        assert_eq!(
+            "eu-west-1",
+            Column::Region.value(&func, Units::Conventional)
+        );
+        assert_eq!(None, Func::default().region());
+        assert_eq!(
+            "-",
+            Column::Region.value(&Func::default(), Units::Conventional)
+        )
+    }
+    #[test]
+    fn func_runtime_family() {
+        let func = |runtime: &str| Func {
+            config: FunctionConfiguration {
+                runtime: Some(runtime.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(RuntimeFamily::Node),
+            func("nodejs10.x").runtime_family()
+        );
+        assert_eq!(
+            Some(RuntimeFamily::Python),
+            func("python2.7").runtime_family()
+        );
+        assert_eq!(
+            Some(RuntimeFamily::Dotnet),
+            func("dotnetcore2.1").runtime_family()
+        );
+        assert_eq!(Some(RuntimeFamily::Go), func("go1.x").runtime_family());
+        assert_eq!(None, func("provided").runtime_family());
+        assert_eq!(None, Func::default().runtime_family())
+    }
+    #[test]
+    fn func_log_group() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("resize-images".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(Some("/aws/lambda/resize-images".into()), func.log_group());
+        assert_eq!(
+            "-",
+            Column::LogGroup.value(&Func::default(), Units::Conventional)
+        )
+    }
+    #[test]
+    fn func_tracing() {
+        let func = |mode: &str| Func {
+            config: FunctionConfiguration {
+                tracing_config: Some(TracingConfigResponse {
+                    mode: Some(mode.into()),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(Tracing::Active, func("Active").tracing());
+        assert_eq!(Tracing::PassThrough, func("PassThrough").tracing());
+        assert_eq!(Tracing::PassThrough, Func::default().tracing());
+        assert_eq!(
+            "active",
+            Column::Tracing.value(&func("Active"), Units::Conventional)
+        )
+    }
+    #[test]
+    fn func_problems() {
+        let healthy = Func {
+            config: FunctionConfiguration {
+                function_name: Some("checkout".into()),
+                runtime: Some("python3.12".into()),
+                dead_letter_config: Some(DeadLetterConfig {
+                    target_arn: Some("arn:aws:sqs:us-east-1:123456789012:dlq".into()),
+                }),
+                tracing_config: Some(TracingConfigResponse {
+                    mode: Some("Active".into()),
+                }),
+                ..Default::default()
+            },
+            tags: vec![Tag {
+                key: "team".into(),
+                value: "payments".into(),
+            }],
+            ..Default::default()
+        };
+        assert!(healthy.problems().is_empty());
+        let neglected = Func {
+            config: FunctionConfiguration {
+                function_name: Some("legacy".into()),
+                runtime: Some("nodejs4.3".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["deprecated runtime", "untagged", "no dlq", "no tracing"],
+            neglected.problems()
+        );
+        let mut out = Vec::new();
+        render_problems(&mut out, &[healthy]);
+        assert!(out.is_empty());
+        render_problems(&mut out, &[neglected]);
+        assert_eq!(
+            "NAME    PROBLEMS\nlegacy  deprecated runtime, untagged, no dlq, no tracing\n",
+            String::from_utf8(out).unwrap()
+        )
+    }
+    #[test]
+    fn func_at_least_timeout() {
+        let func = |timeout| Func {
+            config: FunctionConfiguration {
+                timeout,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func(None).at_least_timeout(None));
+        assert!(func(Some(300)).at_least_timeout(Some(300)));
+        assert!(!func(Some(30)).at_least_timeout(Some(300)));
+        assert!(!func(None).at_least_timeout(Some(300)))
+    }
+    #[test]
+    fn func_at_least_size() {
+        let func = Func {
+            config: FunctionConfiguration {
+                code_size: Some(2048),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func.at_least_size(None));
+        assert!(func.at_least_size(Some(2048)));
+        assert!(!func.at_least_size(Some(2049)));
+        assert!(!Func::default().at_least_size(Some(1)))
+    }
+    #[test]
+    fn output_from_extension() {
+        assert_eq!(
+            Some(Output::Csv),
+            Output::from_extension(Path::new("out/report.csv"))
+        );
+        assert_eq!(
+            Some(Output::Yaml),
+            Output::from_extension(Path::new("report.YML"))
+        );
+        assert_eq!(
+            Some(Output::Jsonl),
+            Output::from_extension(Path::new("report.jsonl"))
+        );
+        assert_eq!(None, Output::from_extension(Path::new("report.json")));
+        assert_eq!(None, Output::from_extension(Path::new("report.xlsx")));
+        assert_eq!(None, Output::from_extension(Path::new("report")))
+    }
+    #[test]
+    fn snapshot_round_trip() {
+        let funcs = vec![Func {
+            config: FunctionConfiguration {
+                function_name: Some("api".into()),
+                code_size: Some(2048),
+                environment: Some(EnvironmentResponse {
+                    variables: Some(
+                        vec![("STAGE".to_string(), "prod".to_string())]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    ..Default::default()
+                }),
+                layers: Some(vec![Layer {
+                    arn: Some("arn:aws:lambda:us-east-1:123456789012:layer:deps:3".into()),
+                    code_size: Some(1024),
+                }]),
+                tracing_config: Some(TracingConfigResponse {
+                    mode: Some("Active".into()),
+                }),
+                ..Default::default()
+            },
+            tags: vec![Tag {
+                key: "team".into(),
+                value: "payments".into(),
+            }],
+            reserved_concurrency: Some(5),
+        }];
+        let captured_at = "2019-06-28T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut out = Vec::new();
+        write_snapshot(&mut out, &funcs, captured_at).unwrap();
+        let snapshot = String::from_utf8(out).unwrap().parse::<Snapshot>().unwrap();
+        assert_eq!("2019-06-28T12:00:00Z", snapshot.captured_at);
+        let loaded = snapshot
+            .fixture
+            .funcs(
+                vec![("team".into(), "payments".into())],
+                Progress::new(false),
+            )
+            .wait()
+            .unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(funcs[0].config, loaded[0].config);
+        assert_eq!(funcs[0].tags, loaded[0].tags);
+        assert_eq!(Some(5), loaded[0].reserved_concurrency);
+        assert!("[]".parse::<Snapshot>().is_err())
+    }
+    #[test]
+    fn fixture_source() {
+        let fixture = r#"[
+            {"Configuration": {"FunctionName": "api", "Runtime": "python3.12", "CodeSize": 2048},
+             "Tags": {"team": "payments", "env": "prod"}},
+            {"Configuration": {"FunctionName": "worker"}, "Tags": {"team": "billing"}},
+            {"Configuration": {"FunctionName": "legacy"}}
+        ]"#
+        .parse::<Fixture>()
+        .unwrap();
+        let names = |tags: Vec<(String, String)>| {
+            fixture
+                .funcs(tags, Progress::new(false))
+                .wait()
+                .unwrap()
+                .iter()
+                .map(|func| func.name().unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["api", "worker", "legacy"], names(vec![]));
+        assert_eq!(
+            vec!["api", "worker"],
+            names(vec![
+                ("team".into(), "payments".into()),
+                ("team".into(), "billing".into())
+            ])
+        );
+        assert_eq!(
+            vec!["api"],
+            names(vec![
+                ("team".into(), "payments".into()),
+                ("env".into(), "prod".into())
+            ])
+        );
+        let api = fixture
+            .funcs(vec![], Progress::new(false))
+            .wait()
+            .unwrap()
+            .remove(0);
+        assert_eq!(Some(2048), api.code_size());
+        assert_eq!(
+            vec!["env", "team"],
+            api.tags
+                .iter()
+                .map(|tag| tag.key.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!("{}".parse::<Fixture>().is_err())
+    }
+    #[test]
+    fn func_enrichment_is_bounded() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let funcs = (0..10)
+            .map(|i| Func {
+                config: FunctionConfiguration {
+                    function_name: Some(format!("fn-{}", i)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let (counter, max) = (in_flight.clone(), peak.clone());
+        let names = for_each_func_concurrent(funcs, 3, move |func| {
+            let (counter, max) = (counter.clone(), max.clone());
+            let mut started = false;
+            future::poll_fn(move || {
+                if !started {
+                    started = true;
+                    let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(count, Ordering::SeqCst);
+                    futures::task::current().notify();
+                    return Ok::<_, ()>(Async::NotReady);
+                }
+                counter.fetch_sub(1, Ordering::SeqCst);
+                Ok(Async::Ready(func.name().unwrap_or_default()))
+            })
+        })
+        .wait()
+        .unwrap();
+        assert_eq!(3, peak.load(Ordering::SeqCst));
+        assert_eq!(0, in_flight.load(Ordering::SeqCst));
+        assert_eq!(
+            (0..10).map(|i| format!("fn-{}", i)).collect::<Vec<_>>(),
+            names
+        )
+    }
+    #[test]
+    fn templates() {
+        let func = Func {
+            config: FunctionConfiguration {
+                function_name: Some("foo".into()),
+                runtime: Some("python3.12".into()),
+                memory_size: Some(128),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let template = "{name}\\t{runtime} {{{memory}}}"
+            .parse::<Template>()
+            .unwrap();
+        assert_eq!(
+            vec![Column::Name, Column::Runtime, Column::Memory],
+            template.columns()
+        );
+        let mut out = Vec::new();
+        render_template(&mut out, &[func], &template, Units::Conventional);
+        assert_eq!(
+            "foo\tpython3.12 {128 MB}\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert!("{colour}"
+            .parse::<Template>()
+            .unwrap_err()
+            .starts_with("unknown placeholder {colour}"));
+        assert!("{name".parse::<Template>().is_err());
+        assert!("name}".parse::<Template>().is_err());
+        assert_eq!(Ok(Template(vec![])), "".parse::<Template>())
+    }
+    #[test]
+    fn func_at_most_size() {
+        let func = Func {
+            config: FunctionConfiguration {
+                code_size: Some(2048),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(func.at_most_size(None));
+        assert!(func.at_most_size(Some(2048)));
+        assert!(!func.at_most_size(Some(2047)));
+        assert!(!Func::default().at_most_size(Some(1)))
+    }
+    #[test]
+    fn lambdas_retry_transient_errors() {
+        assert!(lambdas_retryable(&RusotoError::Service(
+            ListFunctionsError::TooManyRequests("slow down".into())
+        )));
+        assert!(lambdas_retryable(&RusotoError::Service(
+            ListFunctionsError::Service("internal".into())
+        )));
+        assert!(!lambdas_retryable(&RusotoError::Service(
+            ListFunctionsError::InvalidParameterValue("bad".into())
+        )))
+    }
+    #[test]
+    fn paginate_follows_tokens() {
+        let pages = paginate("test", 10, |token: Option<String>| {
+            future::ok(match token.as_deref() {
+                None => (vec![1, 2], Some("2".into())),
+                Some("2") => (vec![3], Some("".into())),
+                _ => (vec![], None),
+            })
+        });
+        assert_eq!(vec![1, 2, 3], pages.concat2().wait().unwrap())
+    }
+    #[test]
+    fn lambdas_deduplicated_across_pages() {
+        let config = |arn: &str| FunctionConfiguration {
+            function_arn: Some(arn.into()),
+            ..Default::default()
+        };
+        let pages = stream::iter_ok::<_, Error>(vec![
+            vec![config("arn:a"), config("arn:b")],
+            vec![config("arn:b"), config("arn:c")],
+        ]);
+        assert_eq!(
+            vec![
+                Some("arn:a".into()),
+                Some("arn:b".into()),
+                Some("arn:c".into())
+            ],
+            unique_lambdas(pages)
+                .wait()
+                .unwrap()
+                .into_iter()
+                .map(|config| config.function_arn)
+                .collect::<Vec<_>>()
+        )
+    }
+    struct NoopNotify;
+    impl Notify for NoopNotify {
+        fn notify(
+            &self,
+            _: usize,
+        ) {
+        }
+    }
+    #[test]
+    fn limiter_holds_slots_until_requests_finish() {
+        let notify = NotifyHandle::from(Arc::new(NoopNotify));
+        let limiter = Limiter::new(Some(1));
+        let mut held = executor::spawn(limiter.limit(future::empty::<(), Error>()));
+        assert!(held.poll_future_notify(&notify, 0).unwrap().is_not_ready());
+        let mut waiting = executor::spawn(limiter.limit(future::ok::<_, Error>(1)));
+        assert!(waiting
+            .poll_future_notify(&notify, 0)
+            .unwrap()
+            .is_not_ready());
+        drop(held);
+        assert_eq!(
+            Async::Ready(1),
+            waiting.poll_future_notify(&notify, 0).unwrap()
+        );
+        let unlimited = Limiter::default();
+        assert_eq!(
+            (1, 2),
+            unlimited
+                .limit(future::ok::<_, Error>(1))
+                .join(unlimited.limit(future::ok(2)))
+                .wait()
+                .unwrap()
+        )
+    }
+    #[test]
+    fn paginate_stops_at_page_limit() {
+        let pages = paginate("test", 3, |_| future::ok((vec![1], Some("stuck".into()))));
+        match pages.concat2().wait() {
+            Err(Error::Pagination("test", 3)) => (),
+            other => panic!("expected page limit error, got {:?}", other),
+        }
+    }
+    #[test]
+    fn progress_counts_fetched_pages() {
+        let progress = Progress::new(false);
+        progress.lambdas(3);
+        progress.mappings(2);
+        progress.lambdas(2);
+        assert_eq!("fetched 5 functions / 2 tag mappings", progress.message());
+        assert_eq!(
+            Some(1),
+            progress.finish_after(future::ok::<_, Error>(1)).wait().ok()
+        )
+    }
+    #[test]
+    fn tags_retry_only_throttling() {
+        assert!(tags_retryable(&RusotoError::Service(
+            GetResourcesError::Throttled("slow down".into())
+        )));
+        assert!(!tags_retryable(&RusotoError::Service(
+            GetResourcesError::InvalidParameter("bad".into())
+        )))
+    }
+    #[test]
+    fn tag_names_with_values() {
+        let tag = |key: &str, value: &str| Tag {
+            key: key.into(),
+            value: value.into(),
+        };
+        let mappings = vec![
+            ResourceTagMapping {
+                tags: Some(vec![tag("team", "foo"), tag("env", "prod")]),
+                ..Default::default()
+            },
+            ResourceTagMapping {
+                tags: Some(vec![tag("team", "bar"), tag("env", "prod")]),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            vec![("env".into(), None), ("team".into(), None)],
+            tag_names(&mappings, false).into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("env".into(), Some("prod".into())),
+                ("team".into(), Some("bar".into())),
+                ("team".into(), Some("foo".into()))
+            ],
+            tag_names(&mappings, true).into_iter().collect::<Vec<_>>()
+        )
+    }
+    #[test]
+    fn fips_endpoints() {
+        let region = fips_region(Region::UsWest2).unwrap();
+        assert_eq!("us-west-2", region.name());
+        assert_eq!(
+            Region::Custom {
+                name: "us-west-2".into(),
+                endpoint: "https://lambda-fips.us-west-2.amazonaws.com".into(),
+            },
+            service_region(region.clone(), "lambda")
+        );
+        assert_eq!(
+            Region::Custom {
+                name: "us-west-2".into(),
+                endpoint: "https://tagging-fips.us-west-2.amazonaws.com".into(),
+            },
+            service_region(region, "tagging")
+        );
+        assert_eq!(Region::EuWest1, service_region(Region::EuWest1, "lambda"));
+        assert_eq!(Err("eu-west-1".into()), fips_region(Region::EuWest1))
+    }
+    #[test]
+    fn fips_regions_in_partition() {
+        let names = |region: Region| {
+            fips_partition_regions(&fips_region(region).unwrap())
+                .iter()
+                .map(|region| region.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["us-east-1", "us-east-2", "us-west-1", "us-west-2"],
+            names(Region::UsEast1)
+        );
+        assert_eq!(
+            vec!["us-gov-east-1", "us-gov-west-1"],
+            names(Region::UsGovWest1)
+        );
+        assert!(fips_partition_regions(&Region::UsEast1).iter().all(is_fips))
+    }
+    #[test]
+    fn profile_settings() {
+        let contents = "[default]\nregion = us-east-1\ncredential_process = sso default\n\n\
+                        # comment\n[profile prod]\nregion=eu-west-1\n[staging]\nregion = \n";
+        assert_eq!(
+            Some("us-east-1".to_string()),
+            profile_setting(contents, "default", "region")
+        );
+        assert_eq!(
+            Some("eu-west-1".to_string()),
+            profile_setting(contents, "prod", "region")
+        );
+        assert_eq!(
+            None,
+            profile_setting(contents, "prod", "credential_process")
+        );
+        assert_eq!(None, profile_setting(contents, "staging", "region"));
+        assert_eq!(None, profile_setting(contents, "dev", "region"))
+    }
+    #[test]
+    fn fips_region_clients() {
+        let region = fips_region(Region::UsEast1).unwrap();
+        let clients =
+            region_clients(credentials(None, None), &region, vec![Region::UsWest2]).unwrap();
+        assert_eq!(2, clients.len());
+        match region_clients(credentials(None, None), &region, vec![Region::EuWest1]) {
+            Err(name) => assert_eq!("eu-west-1", name),
+            Ok(_) => panic!("expected a FIPS error"),
+        }
+        assert!(region_clients(
+            credentials(None, None),
+            &Region::UsEast1,
+            vec![Region::EuWest1]
+        )
+        .is_ok())
+    }
+    #[test]
+    fn region_resolution_order() {
+        let env = |name: &str| match name {
+            "AWS_DEFAULT_REGION" => Some("eu-west-1".to_string()),
+            _ => None,
+        };
+        let profile = || Some("ap-south-1".to_string());
+        assert_eq!(
+            Ok((Region::UsWest2, RegionSource::Flag)),
+            resolve_region_from(Some(Region::UsWest2), Some(Region::UsEast2), env, profile)
+        );
+        assert_eq!(
+            Ok((Region::EuWest1, RegionSource::Env("AWS_DEFAULT_REGION"))),
+            resolve_region_from(None, Some(Region::UsEast2), env, profile)
+        );
+        assert_eq!(
+            Ok((Region::UsEast2, RegionSource::Config)),
+            resolve_region_from(None, Some(Region::UsEast2), |_| None, profile)
+        );
+        assert_eq!(
+            Ok((Region::ApSouth1, RegionSource::Profile)),
+            resolve_region_from(None, None, |_| None, profile)
+        );
+        assert_eq!(
+            Ok((Region::UsEast1, RegionSource::Default)),
+            resolve_region_from(None, None, |_| None, || None)
+        );
+        assert_eq!(
+            Err("nope".to_string()),
+            resolve_region_from(None, None, |_| Some("nope".into()), profile)
+        )
+    }
+    #[test]
+    fn cli_tags_to_filters() {
+        let filters = filters(vec![("foo".into(), "bar".into())]);
+        assert_eq!(
+            filters,
+            vec![TagFilter {
+                key: Some("foo".into()),
+                values: Some(vec!["bar".into()]),
+            }]
+        );
+        let filters = super::filters(vec![
+            ("team".into(), "payments".into()),
+            ("env".into(), "prod".into()),
+            ("team".into(), "billing".into()),
+        ]);
+        assert_eq!(
+            filters,
+            vec![
+                TagFilter {
+                    key: Some("team".into()),
+                    values: Some(vec!["payments".into(), "billing".into()]),
+                },
+                TagFilter {
+                    key: Some("env".into()),
+                    values: Some(vec!["prod".into()]),
+                }
+            ]
+        );
+        let filters = super::filters(vec![
+            ("team".into(), "a,b".into()),
+            ("team".into(), "c".into()),
+            ("note".into(), r"x\,y".into()),
+        ]);
+        assert_eq!(
+            filters,
+            vec![
+                TagFilter {
+                    key: Some("team".into()),
+                    values: Some(vec!["a".into(), "b".into(), "c".into()]),
+                },
+                TagFilter {
+                    key: Some("note".into()),
+                    values: Some(vec!["x,y".into()]),
+                }
+            ]
+        )
+    }
+}
@@ -1,32 +1,56 @@
-//! AWS Lambda stock management
+//! Command line interface for browsing AWS lambdas
 
+use chrono::{NaiveDate, Utc};
+use clap::Shell;
 use failure::Fail;
-use futures::future::{self, Future};
-use futures_backoff::Strategy;
-use humansize::{file_size_opts as options, FileSize};
-use rusoto_core::{credential::ChainProvider, request::HttpClient, RusotoError};
-use rusoto_lambda::{
-    FunctionConfiguration, Lambda, LambdaClient, ListFunctionsError, ListFunctionsRequest,
+use futures::{
+    future::{self, Future},
+    Stream,
 };
-use rusoto_resourcegroupstaggingapi::{
-    GetResourcesError, GetResourcesInput, ResourceGroupsTaggingApi, ResourceGroupsTaggingApiClient,
-    ResourceTagMapping, Tag, TagFilter,
+use lambstock::{
+    all_region_funcs, assume_role, credential_ttl, credentials, fips_partition_regions,
+    fips_region, function, group_funcs, group_funcs_by_tag, lambda_client, parse_region,
+    parse_size, purge_versions, render_aliases, render_cost, render_diff, render_func_detail,
+    render_funcs, render_go_migration, render_grouped, render_layers, render_missing_tags,
+    render_near_limit, render_outliers, render_problems, render_purge, render_runtimes,
+    render_stale_layers, render_stats, render_storage, render_summary, render_tag_values,
+    render_tags, render_template, render_versions, render_vpcs, resolve_region, runtime_counts,
+    sort_funcs, split_tag_values, stale_layers, tag_mappings, tag_names, tags_client, with_aliases,
+    with_concurrency, with_purgeable_versions, with_versions, write_snapshot, Column, Error,
+    ErrorFormat, Fixture, Func, FuncDiff, GroupBy, Limiter, Live, Output, Progress, RegionSource,
+    Retries, RuntimeFamily, Select, Snapshot, Sort, Source, TagFilterMode, Template, TextLayout,
+    Tracing, Units, CODE_STORAGE_QUOTA_GB, LAMBDA_REGIONS, MIN_CREDENTIAL_TTL_SECS, NEAR_LIMIT_PCT,
+    OUTLIER_MEMORY_MB, OUTLIER_TIMEOUT_SECS, STORAGE_PRICE_PER_GB,
 };
+use regex::Regex;
+use rusoto_core::{ProvideAwsCredentials, Region, RusotoError};
+use rusoto_lambda::GetFunctionError;
+use serde::{de, Deserialize, Deserializer};
+use serde_json::json;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashSet},
+    env,
     error::Error as StdError,
     fmt,
+    fs::{self, File},
     io::{self, Write},
+    path::{Path, PathBuf},
     process::exit,
     str::FromStr,
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use structopt::StructOpt;
-use tabwriter::TabWriter;
-use tokio::runtime::Runtime;
+use tokio::{
+    runtime::Runtime,
+    timer::{Delay, Timeout},
+};
+
+/// ANSI sequence clearing the terminal and moving the cursor home, between `--watch` refreshes
+const CLEAR_SCREEN: &str = "\x1B[2J\x1B[H";
 
-mod error;
-use crate::error::Error;
+/// Maximum number of per function lookups, e.g. of reserved concurrency, in flight at once
+const CONCURRENCY_PARALLELISM: usize = 8;
 
 fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<StdError>>
 where
@@ -41,33 +65,95 @@ where
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+fn parse_page_size(
+    s: &str,
+    max: i64,
+) -> Result<i64, String> {
+    let size = s
+        .parse::<i64>()
+        .map_err(|_| format!("invalid page size: `{}` is not a number", s))?;
+    check_page_size(size, max)
+}
+
+fn check_page_size(
+    size: i64,
+    max: i64,
+) -> Result<i64, String> {
+    if size < 1 || size > max {
+        return Err(format!(
+            "invalid page size: {} is outside the API limit of 1 to {}",
+            size, max
+        ));
+    }
+    Ok(size)
+}
+
+fn parse_region_arg(s: &str) -> Result<Region, String> {
+    parse_region(s).map_err(|name| Error::InvalidRegion(name).to_string())
+}
+
+fn parse_max_concurrency(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) | Err(_) => Err(format!(
+            "invalid concurrency limit: `{}` is not a positive number",
+            s
+        )),
+        Ok(max) => Ok(max),
+    }
+}
+
+fn parse_lambda_page_size(s: &str) -> Result<i64, String> {
+    parse_page_size(s, 100)
+}
+
+fn parse_tags_page_size(s: &str) -> Result<i64, String> {
+    parse_page_size(s, 100)
+}
+
+/// Parse a calendar date in `YYYY-MM-DD` form
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date: `{}` is not in YYYY-MM-DD form", s))
+}
+
 #[derive(Debug, PartialEq)]
-enum Sort {
-    Name,
-    Runtime,
-    CodeSize,
+enum Color {
+    Always,
+    Auto,
+    Never,
 }
 
-impl Sort {
+impl Color {
     fn variants() -> &'static [&'static str] {
-        &["name", "runtime", "codesize"]
+        &["always", "auto", "never"]
+    }
+
+    /// Return true if output should be colorized
+    ///
+    /// In auto mode color is disabled when `NO_COLOR` is set or stdout is not a terminal
+    fn enabled(&self) -> bool {
+        match self {
+            Color::Always => true,
+            Color::Auto => env::var_os("NO_COLOR").is_none() && atty::is(atty::Stream::Stdout),
+            Color::Never => false,
+        }
     }
 }
 
-impl FromStr for Sort {
+impl FromStr for Color {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "name" => Ok(Sort::Name),
-            "runtime" => Ok(Sort::Runtime),
-            "codesize" => Ok(Sort::CodeSize),
+            "always" => Ok(Color::Always),
+            "auto" => Ok(Color::Auto),
+            "never" => Ok(Color::Never),
             _ => Err("no match"),
         }
     }
 }
 
-impl fmt::Display for Sort {
+impl fmt::Display for Color {
     fn fmt(
         &self,
         f: &mut fmt::Formatter,
@@ -76,298 +162,2230 @@ impl fmt::Display for Sort {
             f,
             "{}",
             match self {
-                Sort::Name => "name",
-                Sort::Runtime => "runtime",
-                Sort::CodeSize => "codesize",
+                Color::Always => "always",
+                Color::Auto => "auto",
+                Color::Never => "never",
             }
         )
     }
 }
 
+/// Deserialize an optional config value with its `FromStr` impl, as the cli parses it
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => value.parse().map(Some).map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Deserialize an optional page size, held to the same API limits as the cli
+fn deserialize_page_size<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<i64>::deserialize(deserializer)? {
+        Some(size) => check_page_size(size, 100)
+            .map(Some)
+            .map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Defaults read from a config file, overridden by cli flags
+#[derive(Deserialize, Default, PartialEq, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    #[serde(deserialize_with = "deserialize_from_str")]
+    region: Option<Region>,
+    profile: Option<String>,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    sort: Option<Sort>,
+    #[serde(deserialize_with = "deserialize_page_size")]
+    lambda_page_size: Option<i64>,
+    #[serde(deserialize_with = "deserialize_page_size")]
+    tags_page_size: Option<i64>,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    units: Option<Units>,
+}
+
+impl Command {
+    /// Merge the tag filters of a `--tags-file` into those passed with `--tag`
+    fn load_tags_file(&mut self) -> Result<(), String> {
+        match self {
+            Command::List {
+                tags, tags_file, ..
+            }
+            | Command::Count { tags, tags_file }
+            | Command::Stats {
+                tags, tags_file, ..
+            }
+            | Command::Aliases { tags, tags_file }
+            | Command::StaleLayers { tags, tags_file }
+            | Command::PurgeVersions {
+                tags, tags_file, ..
+            }
+            | Command::Versions {
+                tags, tags_file, ..
+            } => {
+                if let Some(path) = tags_file.take() {
+                    tags.extend(read_tags_file(&path)?)
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Path of the config file read when `--config` is not provided
+    fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".config").join("lambstock.toml"))
+    }
+
+    /// Load config from the given path, or the default path where a missing file yields no defaults
+    fn load(path: Option<&PathBuf>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(path) => (path.clone(), true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(ref err) if !required && err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        };
+        toml::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+/// Parse `key=value` tag filters, one per line, skipping blank lines and `#` comments
+fn parse_tags_lines(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut tags = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tag = parse_key_val(line).map_err(|err| format!("line {}: {}", index + 1, err))?;
+        tags.push(tag);
+    }
+    Ok(tags)
+}
+
+/// Read tag filters from a file in the format of `parse_tags_lines`
+fn read_tags_file(path: &Path) -> Result<Vec<(String, String)>, String> {
+    fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| parse_tags_lines(&contents))
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Parse function ARNs, one per line, skipping blank lines and `#` comments
+fn parse_arn_lines(contents: &str) -> BTreeSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
 /// CLI options
 #[derive(StructOpt, PartialEq, Debug)]
 #[structopt(name = "lambstock", about = "stock management for your AWS lambda")]
-enum Options {
-    #[structopt(name = "list", alias = "ls", about = "List lambdas")]
+struct Options {
+    #[structopt(
+        long = "region",
+        help = "AWS region to query, defaults to AWS_REGION, AWS_DEFAULT_REGION, then your configured region",
+        parse(try_from_str = "parse_region_arg"),
+        raw(global = "true")
+    )]
+    region: Option<Region>,
+    #[structopt(
+        long = "config",
+        help = "Config file supplying defaults for options, defaults to ~/.config/lambstock.toml",
+        parse(from_os_str),
+        raw(global = "true")
+    )]
+    config: Option<PathBuf>,
+    #[structopt(
+        long = "profile",
+        help = "Named AWS credentials profile to authenticate with",
+        raw(global = "true")
+    )]
+    profile: Option<String>,
+    #[structopt(
+        long = "role-arn",
+        help = "ARN of an IAM role to assume with your base credentials, e.g. to query another account",
+        raw(global = "true")
+    )]
+    role_arn: Option<String>,
+    #[structopt(
+        long = "role-session-name",
+        default_value = "lambstock",
+        help = "Session name to identify the assumed role session by",
+        raw(global = "true")
+    )]
+    role_session_name: String,
+    #[structopt(
+        long = "lambda-page-size",
+        help = "Number of functions to request per Lambda API page, 1 to 100, defaults to 100",
+        parse(try_from_str = "parse_lambda_page_size"),
+        raw(global = "true")
+    )]
+    lambda_page_size: Option<i64>,
+    #[structopt(
+        long = "tags-page-size",
+        help = "Number of resources to request per tagging API page, 1 to 100, defaults to 50",
+        parse(try_from_str = "parse_tags_page_size"),
+        raw(global = "true")
+    )]
+    tags_page_size: Option<i64>,
+    #[structopt(
+        long = "max-retries",
+        default_value = "15",
+        help = "Number of times to retry throttled API requests, 0 to try only once",
+        raw(global = "true")
+    )]
+    max_retries: usize,
+    #[structopt(
+        long = "max-concurrency",
+        help = "Maximum number of API requests in flight at once, unlimited by default",
+        parse(try_from_str = "parse_max_concurrency"),
+        raw(global = "true")
+    )]
+    max_concurrency: Option<usize>,
+    #[structopt(
+        long = "backoff-ms",
+        default_value = "100",
+        help = "Delay in milliseconds before the first retry, doubling with each retry after",
+        raw(global = "true")
+    )]
+    backoff_ms: u64,
+    #[structopt(
+        long = "credential-timeout-ms",
+        default_value = "200",
+        help = "Timeout in milliseconds for each source of default credentials, e.g. instance metadata, 0 to use rusoto's default",
+        raw(global = "true")
+    )]
+    credential_timeout_ms: u64,
+    #[structopt(
+        long = "min-credential-ttl",
+        help = "Fail up front if temporary credentials, e.g. an assumed role session, expire within this many seconds",
+        raw(global = "true")
+    )]
+    min_credential_ttl: Option<u64>,
+    #[structopt(
+        long = "timeout",
+        help = "Give up on the whole command after this many seconds, including retries, exiting with an error",
+        raw(global = "true")
+    )]
+    timeout: Option<u64>,
+    #[structopt(
+        long = "units",
+        help = "Units to display sizes in, binary for KiB and MiB or decimal for powers of 1000, defaults to conventional",
+        raw(
+            possible_values = "&Units::variants()",
+            case_insensitive = "true",
+            global = "true"
+        )
+    )]
+    units: Option<Units>,
+    #[structopt(
+        short = "v",
+        long = "verbose",
+        help = "Log more detail, repeat for more, e.g. -vv for retry diagnostics. RUST_LOG wins when set",
+        parse(from_occurrences),
+        raw(global = "true")
+    )]
+    verbose: u8,
+    #[structopt(
+        long = "output-file",
+        help = "Write results to this file instead of stdout",
+        parse(from_os_str),
+        raw(global = "true")
+    )]
+    output_file: Option<PathBuf>,
+    #[structopt(
+        long = "no-progress",
+        help = "Don't show fetch progress on stderr, which is only shown when stderr is a terminal",
+        raw(global = "true")
+    )]
+    no_progress: bool,
+    #[structopt(
+        long = "error-format",
+        help = "Format of errors printed to stderr, json printing one object with the error and its causes",
+        default_value = "text",
+        raw(
+            possible_values = "&ErrorFormat::variants()",
+            case_insensitive = "true",
+            global = "true"
+        )
+    )]
+    error_format: ErrorFormat,
+    #[structopt(
+        long = "show-region",
+        help = "Print the region queried, and where it was resolved from, to stderr",
+        raw(global = "true")
+    )]
+    show_region: bool,
+    #[structopt(
+        long = "fips",
+        help = "Connect to the FIPS endpoints of Lambda and the tagging API, which need a region with FIPS endpoints",
+        raw(global = "true")
+    )]
+    fips: bool,
+    #[structopt(
+        long = "fixture",
+        help = "Read lambdas from a JSON file of GetFunction responses instead of AWS, for demos and tests",
+        parse(from_os_str),
+        raw(global = "true", env = r#""LAMBSTOCK_FIXTURE""#)
+    )]
+    fixture: Option<PathBuf>,
+    #[structopt(
+        long = "snapshot-in",
+        help = "Read lambdas from a file written by list --snapshot-out instead of AWS, printing when it was captured",
+        parse(from_os_str),
+        conflicts_with = "fixture",
+        raw(global = "true")
+    )]
+    snapshot_in: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt, PartialEq, Debug)]
+enum Command {
+    #[structopt(
+        name = "list",
+        alias = "ls",
+        about = "List lambdas",
+        after_help = "EXIT CODES:\n    0    Success\n    1    Failed to query AWS\n    2    No functions matched, with --fail-on-empty\n    130  Interrupted with ctrl-c"
+    )]
     List {
         #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
         tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+        #[structopt(
+            long = "tag-filter-mode",
+            default_value = "all",
+            help = "Whether functions must match all --tag filters, checked by the tagging API, or any of them, checked after listing every function",
+            raw(
+                possible_values = "&TagFilterMode::variants()",
+                case_insensitive = "true"
+            )
+        )]
+        tag_filter_mode: TagFilterMode,
         #[structopt(
             short = "s",
             long = "sort",
-            default_value = "name",
+            help = "Field to sort by, defaults to name. codesize, or size, is the deployment package size, memory the configured memory, lastmodified the last deploy and tagcount the number of tags",
             raw(possible_values = "&Sort::variants()", case_insensitive = "true")
         )]
-        sort: Sort,
+        sort: Option<Sort>,
+        #[structopt(
+            short = "r",
+            long = "reverse",
+            help = "Reverse the sort order, e.g. to list the largest functions first"
+        )]
+        reverse: bool,
+        #[structopt(
+            long = "natural-sort",
+            help = "Sort names numeric aware, e.g. worker-2 before worker-10"
+        )]
+        natural_sort: bool,
+        #[structopt(
+            long = "ignore-case",
+            help = "Sort names case insensitively, e.g. Foo next to foo"
+        )]
+        ignore_case: bool,
+        #[structopt(
+            long = "limit",
+            help = "Only render the first N functions after sorting",
+            raw(conflicts_with_all = r#"&["group_by", "sum_by"]"#)
+        )]
+        limit: Option<usize>,
+        #[structopt(
+            short = "o",
+            long = "output",
+            default_value = "text",
+            raw(possible_values = "&Output::variants()", case_insensitive = "true")
+        )]
+        output: Output,
+        #[structopt(
+            short = "n",
+            long = "name",
+            help = "Only list functions whose name matches this regular expression"
+        )]
+        name: Option<String>,
+        #[structopt(
+            long = "summary",
+            help = "Append a line with the total function count and code size"
+        )]
+        summary: bool,
+        #[structopt(
+            long = "deprecated-only",
+            help = "Only list functions running on a deprecated runtime"
+        )]
+        deprecated_only: bool,
+        #[structopt(
+            long = "go-migration",
+            help = "Only list functions on the retired go1.x runtime, with their handler and the OS only runtime to rebuild them for, printing nothing when there are none",
+            raw(
+                conflicts_with_all = r#"&["layers", "vpc", "problems_only", "group_by", "sum_by", "template"]"#
+            )
+        )]
+        go_migration: bool,
+        #[structopt(
+            long = "near-limit",
+            help = "Only list functions whose zipped code is close to the 50 MB direct upload limit, with the headroom each has left, printing nothing when there are none",
+            raw(
+                conflicts_with_all = r#"&["layers", "vpc", "problems_only", "go_migration", "group_by", "sum_by", "template"]"#
+            )
+        )]
+        near_limit: bool,
+        #[structopt(
+            long = "near-limit-pct",
+            help = "Percentage of the direct upload limit within which --near-limit lists a function, defaults to 10",
+            raw(requires = r#""near_limit""#)
+        )]
+        near_limit_pct: Option<f64>,
+        #[structopt(
+            long = "outliers",
+            help = "Only list functions configured with both a high timeout and high memory, the costliest when invocations hang, with the reason each was flagged",
+            raw(
+                conflicts_with_all = r#"&["layers", "vpc", "problems_only", "go_migration", "near_limit", "group_by", "sum_by", "template"]"#
+            )
+        )]
+        outliers: bool,
+        #[structopt(
+            long = "outlier-timeout",
+            help = "Timeout in seconds above which --outliers considers a function, defaults to 60",
+            raw(requires = r#""outliers""#)
+        )]
+        outlier_timeout: Option<i64>,
+        #[structopt(
+            long = "outlier-memory",
+            help = "Memory in MB above which --outliers considers a function, defaults to 1024",
+            raw(requires = r#""outliers""#)
+        )]
+        outlier_memory: Option<i64>,
+        #[structopt(
+            long = "color",
+            default_value = "auto",
+            raw(possible_values = "&Color::variants()", case_insensitive = "true")
+        )]
+        color: Color,
+        #[structopt(
+            short = "c",
+            long = "columns",
+            help = "Comma separated list of columns to render, in order",
+            raw(
+                possible_values = "&Column::variants()",
+                use_delimiter = "true",
+                case_insensitive = "true"
+            )
+        )]
+        columns: Vec<Column>,
+        #[structopt(long = "with-tags", help = "Append a column of each function's tags")]
+        with_tags: bool,
+        #[structopt(
+            long = "tags-width",
+            help = "Truncate the tags column of text output to this many characters"
+        )]
+        tags_width: Option<usize>,
+        #[structopt(
+            long = "padding",
+            help = "Spaces between columns of text output",
+            default_value = "2"
+        )]
+        padding: usize,
+        #[structopt(
+            long = "min-width",
+            help = "Minimum width of each column of text output, before padding",
+            default_value = "2"
+        )]
+        min_width: usize,
+        #[structopt(
+            long = "template",
+            help = "Render a line per function from a format string of column placeholders, e.g. '{name}\\t{runtime}\\t{memory}', writing {{ and }} for literal braces",
+            raw(
+                conflicts_with_all = r#"&["columns", "layers", "vpc", "problems_only", "group_by", "sum_by"]"#
+            )
+        )]
+        template: Option<Template>,
+        #[structopt(
+            long = "no-header",
+            help = "Leave out the header row of text, csv, markdown and table output"
+        )]
+        no_header: bool,
+        #[structopt(
+            long = "concurrency",
+            help = "Add a column of reserved concurrency, fetched with an extra API request per function"
+        )]
+        concurrency: bool,
+        #[structopt(
+            long = "log-group",
+            help = "Append a column of each function's CloudWatch log group"
+        )]
+        log_group: bool,
+        #[structopt(
+            long = "layers",
+            help = "List the layers attached to each function instead"
+        )]
+        layers: bool,
+        #[structopt(
+            long = "vpc",
+            help = "List the VPC, subnet count and security group count of each function instead",
+            conflicts_with = "layers"
+        )]
+        vpc: bool,
+        #[structopt(
+            long = "problems-only",
+            help = "Only list functions with a deprecated runtime, no tags, no dead letter queue or no active tracing, naming the problems, printing nothing when none have any",
+            raw(conflicts_with_all = r#"&["layers", "vpc", "group_by", "sum_by"]"#)
+        )]
+        problems_only: bool,
+        #[structopt(
+            long = "min-size",
+            help = "Only list functions with at least this code size, e.g. 10MB",
+            parse(try_from_str = "parse_size")
+        )]
+        min_size: Option<i64>,
+        #[structopt(
+            long = "max-size",
+            help = "Only list functions with at most this code size, e.g. 50MB",
+            parse(try_from_str = "parse_size")
+        )]
+        max_size: Option<i64>,
+        #[structopt(
+            long = "sha",
+            help = "Only list functions whose code has this SHA256 hash, e.g. to confirm a rollout"
+        )]
+        sha: Option<String>,
+        #[structopt(
+            long = "include-arns-from-file",
+            help = "Only list functions whose ARN is listed in this file, one per line, with blank lines and # comments ignored",
+            parse(from_os_str)
+        )]
+        include_arns_from_file: Option<PathBuf>,
+        #[structopt(
+            long = "report-missing-arns",
+            help = "Warn about each ARN in --include-arns-from-file that matched no function",
+            raw(requires = r#""include_arns_from_file""#)
+        )]
+        report_missing_arns: bool,
+        #[structopt(
+            long = "min-timeout",
+            help = "Only list functions with a timeout of at least this many seconds"
+        )]
+        min_timeout: Option<i64>,
+        #[structopt(
+            long = "search",
+            help = "Only list functions whose description contains this text, ignoring case"
+        )]
+        search: Option<String>,
+        #[structopt(
+            long = "select",
+            help = "Only list functions matching an expression over name, runtime, handler, description, size, memory, timeout and tag:<key>, e.g. 'runtime ~ \"nodejs\" && size > 10MB'"
+        )]
+        select: Option<Select>,
+        #[structopt(
+            long = "group-by",
+            help = "Print function counts and total code size per group instead",
+            conflicts_with = "layers",
+            raw(possible_values = "&GroupBy::variants()", case_insensitive = "true")
+        )]
+        group_by: Option<GroupBy>,
+        #[structopt(
+            long = "sum-by",
+            help = "Print function counts and total code size per value of this tag key instead, e.g. team",
+            raw(conflicts_with_all = r#"&["layers", "group_by"]"#)
+        )]
+        sum_by: Option<String>,
+        #[structopt(
+            long = "fail-on-empty",
+            help = "Exit with code 2 when no functions match the filters"
+        )]
+        fail_on_empty: bool,
+        #[structopt(
+            long = "exclude-tag",
+            help = "Drop functions carrying this tag, e.g. env=sandbox",
+            parse(try_from_str = "parse_key_val")
+        )]
+        exclude_tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tag-key",
+            help = "Only list functions carrying this tag key with any value, unlike --tag which also matches the value",
+            conflicts_with = "untagged"
+        )]
+        tag_keys: Vec<String>,
+        #[structopt(
+            long = "modified-before",
+            help = "Only list functions last modified before this UTC date, e.g. 2023-01-01",
+            parse(try_from_str = "parse_date")
+        )]
+        modified_before: Option<NaiveDate>,
+        #[structopt(
+            long = "modified-after",
+            help = "Only list functions last modified on or after this UTC date, e.g. 2023-01-01",
+            parse(try_from_str = "parse_date")
+        )]
+        modified_after: Option<NaiveDate>,
+        #[structopt(
+            long = "untagged",
+            help = "Only list functions without any tags",
+            conflicts_with = "tags"
+        )]
+        untagged: bool,
+        #[structopt(
+            long = "all-regions",
+            help = "List functions across every commercial region, adding a region column"
+        )]
+        all_regions: bool,
+        #[structopt(
+            long = "region-concurrency",
+            default_value = "4",
+            help = "Number of regions --all-regions scans at once",
+            parse(try_from_str = "parse_max_concurrency")
+        )]
+        region_concurrency: usize,
+        #[structopt(
+            long = "tracing",
+            help = "Only list functions with this X-Ray tracing mode",
+            raw(possible_values = "&Tracing::variants()", case_insensitive = "true")
+        )]
+        tracing: Option<Tracing>,
+        #[structopt(
+            long = "tracing-disabled",
+            help = "Only list functions without active X-Ray tracing",
+            conflicts_with = "tracing"
+        )]
+        tracing_disabled: bool,
+        #[structopt(
+            long = "runtime-family",
+            help = "Only list functions with a runtime of this language, whatever its version",
+            raw(
+                possible_values = "&RuntimeFamily::variants()",
+                case_insensitive = "true"
+            )
+        )]
+        runtime_family: Option<RuntimeFamily>,
+        #[structopt(
+            long = "has-env",
+            help = "Only list functions that define environment variables"
+        )]
+        has_env: bool,
+        #[structopt(
+            long = "no-env",
+            help = "Only list functions without environment variables",
+            conflicts_with = "has_env"
+        )]
+        no_env: bool,
+        #[structopt(
+            long = "watch",
+            help = "Refresh the listing every this many seconds until interrupted, clearing the screen in between. --timeout bounds each refresh",
+            conflicts_with = "fail_on_empty"
+        )]
+        watch: Option<u64>,
+        #[structopt(
+            long = "also-write",
+            help = "Also render the results to this file, in the format its extension names, e.g. report.csv, repeating for more files. Results are fetched once for all of them, and always listed one function per row",
+            parse(try_from_str = "parse_sink"),
+            number_of_values = 1,
+            conflicts_with = "watch"
+        )]
+        also_write: Vec<Sink>,
+        #[structopt(
+            long = "snapshot-out",
+            help = "Also save every fetched function, before client-side filters, to this file for later runs with --snapshot-in",
+            parse(from_os_str),
+            conflicts_with = "watch"
+        )]
+        snapshot_out: Option<PathBuf>,
+        #[structopt(long = "cost", help = "Also print an estimated monthly storage cost")]
+        cost: bool,
+        #[structopt(
+            long = "price-per-gb",
+            help = "US dollar price per GB-month used to estimate storage cost, defaults to 0.10",
+            raw(requires = r#""cost""#)
+        )]
+        price_per_gb: Option<f64>,
+    },
+    #[structopt(name = "count", about = "Count lambdas")]
+    Count {
+        #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
+        tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+    },
+    #[structopt(name = "stats", about = "Summarize lambdas across the account")]
+    Stats {
+        #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
+        tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+        #[structopt(long = "cost", help = "Also print an estimated monthly storage cost")]
+        cost: bool,
+        #[structopt(
+            long = "price-per-gb",
+            help = "US dollar price per GB-month used to estimate storage cost, defaults to 0.10",
+            raw(requires = r#""cost""#)
+        )]
+        price_per_gb: Option<f64>,
+    },
+    #[structopt(
+        name = "check",
+        about = "Check lambdas carry required tags",
+        after_help = "EXIT CODES:\n    0    Every function carries the required tags\n    1    Failed to query AWS\n    2    Some functions are missing required tags\n    130  Interrupted with ctrl-c"
+    )]
+    Check {
+        #[structopt(
+            short = "r",
+            long = "require",
+            help = "Tag key every function must carry",
+            raw(required = "true")
+        )]
+        required: Vec<String>,
+    },
+    #[structopt(
+        name = "diff",
+        about = "Compare lambdas matching two tag filters, e.g. to verify a migration"
+    )]
+    Diff {
+        #[structopt(
+            long = "left",
+            help = "Tag filter for the left side, repeated to match several tags",
+            parse(try_from_str = "parse_key_val")
+        )]
+        left: Vec<(String, String)>,
+        #[structopt(
+            long = "right",
+            help = "Tag filter for the right side, repeated to match several tags",
+            parse(try_from_str = "parse_key_val")
+        )]
+        right: Vec<(String, String)>,
+    },
+    #[structopt(
+        name = "aliases",
+        about = "List the aliases of lambdas, fetched with an extra API request per function"
+    )]
+    Aliases {
+        #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
+        tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "versions",
+        about = "Count the published versions of lambdas, fetched with extra API requests per function"
+    )]
+    Versions {
+        #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
+        tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+        #[structopt(
+            long = "color",
+            default_value = "auto",
+            raw(possible_values = "&Color::variants()", case_insensitive = "true")
+        )]
+        color: Color,
+    },
+    #[structopt(
+        name = "storage",
+        about = "Total the code size of all lambdas and their versions against the account quota"
+    )]
+    Storage {
+        #[structopt(
+            long = "limit-gb",
+            help = "Code storage quota in GB, for accounts with a raised quota, defaults to 75"
+        )]
+        limit_gb: Option<f64>,
+    },
+    #[structopt(
+        name = "stale-layers",
+        about = "List lambdas attached to older versions of layers than the latest published"
+    )]
+    StaleLayers {
+        #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
+        tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "purge-versions",
+        about = "Delete old published versions of lambdas, only listing them unless --apply is passed"
+    )]
+    PurgeVersions {
+        #[structopt(short = "t", long = "tag", parse(try_from_str = "parse_key_val"))]
+        tags: Vec<(String, String)>,
+        #[structopt(
+            long = "tags-file",
+            help = "File of key=value tag filters, one per line, merged with --tag",
+            parse(from_os_str)
+        )]
+        tags_file: Option<PathBuf>,
+        #[structopt(
+            long = "keep",
+            help = "Number of the newest published versions to keep per function, on top of any an alias points at"
+        )]
+        keep: usize,
+        #[structopt(
+            long = "apply",
+            help = "Delete the listed versions instead of only listing them"
+        )]
+        apply: bool,
+    },
+    #[structopt(name = "show", about = "Show details of a single lambda")]
+    Show {
+        #[structopt(help = "Name of the lambda function")]
+        name: String,
+        #[structopt(long = "env", help = "Include environment variable names")]
+        env: bool,
+        #[structopt(
+            long = "show-values",
+            help = "Include environment variable values, which may contain secrets",
+            raw(requires = r#""env""#)
+        )]
+        show_values: bool,
+    },
+    #[structopt(name = "completions", about = "Generate shell completion scripts")]
+    Completions {
+        #[structopt(
+            help = "Shell to generate completions for",
+            raw(possible_values = "&Shell::variants()")
+        )]
+        shell: String,
     },
+    #[structopt(
+        name = "runtimes",
+        about = "Count lambdas using each runtime, most used first"
+    )]
+    Runtimes,
     #[structopt(name = "tags", about = "List lambdas tags")]
-    Tags,
+    Tags {
+        #[structopt(
+            long = "values",
+            help = "List the distinct values of each tag key in aligned columns rather than only tag keys"
+        )]
+        values: bool,
+    },
 }
 
-/// A single lambda function with associated tags
-#[derive(Debug, Default)]
-struct Func {
-    config: FunctionConfiguration,
-    tags: Vec<Tag>,
+/// Label a tag filter for display, e.g. `team=a,env=prod`
+fn tag_filter_label(tags: &[(String, String)]) -> String {
+    if tags.is_empty() {
+        return "all functions".into();
+    }
+    tags.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
-impl Func {
-    /// Return size of function for human display
-    fn human_size(&self) -> String {
-        self.code_size()
-            .unwrap_or_default()
-            .file_size(options::CONVENTIONAL)
-            .unwrap_or_default()
+/// Return the log level for lambstock's own messages given the number of `-v` flags
+fn log_level(verbose: u8) -> log::LevelFilter {
+    match verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
     }
+}
 
-    fn name(&self) -> Option<String> {
-        self.config.function_name.clone()
+/// Initialize logging, deferring to `RUST_LOG` when it is set
+fn init_logging(verbose: u8) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if env::var_os("RUST_LOG").is_none() {
+        builder
+            .filter_level(log::LevelFilter::Warn)
+            .filter_module("lambstock", log_level(verbose));
     }
+    builder.init()
+}
 
-    fn runtime(&self) -> Option<String> {
-        self.config.runtime.clone()
+/// Print an error and its causes to stderr, as lines of text or a single JSON object
+fn print_error(
+    err: &Error,
+    format: ErrorFormat,
+) {
+    if let Error::AllRegions(failures) = err {
+        for (region, err) in failures {
+            print_region_error(region, err, format)
+        }
     }
+    match format {
+        ErrorFormat::Text => {
+            if err.cause().is_none() {
+                eprintln!("{}", err);
+            }
+            for cause in Fail::iter_causes(err) {
+                eprintln!("{}", cause);
+            }
+            if let Some(request_id) = err.request_id() {
+                eprintln!("request id: {}", request_id);
+            }
+        }
+        ErrorFormat::Json => eprintln!("{}", error_json(err)),
+    }
+}
+
+/// Print the error of a region skipped by a multi-region scan to stderr, naming the region
+fn print_region_error(
+    region: &str,
+    err: &Error,
+    format: ErrorFormat,
+) {
+    match format {
+        ErrorFormat::Text => {
+            eprintln!("failed to list lambdas in {}", region);
+            print_error(err, format)
+        }
+        ErrorFormat::Json => {
+            let mut value = error_json(err);
+            value["region"] = json!(region);
+            eprintln!("{}", value)
+        }
+    }
+}
 
-    fn code_size(&self) -> Option<i64> {
-        self.config.code_size
+/// Describe an error as a JSON object of its message and the messages of its causes, along
+/// with the AWS request id when there is one
+fn error_json(err: &Error) -> serde_json::Value {
+    let mut value = json!({
+        "error": err.to_string(),
+        "causes": Fail::iter_causes(err)
+            .map(|cause| cause.to_string())
+            .collect::<Vec<_>>(),
+    });
+    if let Some(request_id) = err.request_id() {
+        value["request_id"] = json!(request_id);
     }
+    value
 }
 
-fn filters(tags: Vec<(String, String)>) -> Vec<TagFilter> {
-    tags.into_iter().fold(Vec::new(), |mut filters, (k, v)| {
-        filters.push(TagFilter {
-            key: Some(k),
-            values: Some(vec![v]),
-        });
-        filters
-    })
+/// Print an error and its causes to stderr, then exit, with 130 when interrupted as shells do
+fn report(
+    err: Error,
+    format: ErrorFormat,
+) -> ! {
+    print_error(&err, format);
+    match err {
+        Error::Interrupted => exit(130),
+        _ => exit(1),
+    }
 }
 
-fn lambdas(
-    client: LambdaClient,
-    marker: Option<String>,
-) -> Box<Future<Item = Vec<FunctionConfiguration>, Error = RusotoError<ListFunctionsError>> + Send>
+/// Run a future to completion on the runtime, failing with `Error::Interrupted` if ctrl-c is
+/// pressed before it completes, or `Error::TimedOut` if it is still running at the deadline
+fn block_on<F>(
+    rt: &mut Runtime,
+    deadline: Option<Instant>,
+    future: F,
+) -> Result<F::Item, F::Error>
+where
+    F: Future + Send + 'static,
+    F::Item: Send + 'static,
+    F::Error: From<Error> + Send + 'static,
 {
-    let client_inner = client.clone();
-    Box::new(
-        backoff()
-            .retry_if(
-                move || {
-                    client_inner.list_functions(ListFunctionsRequest {
-                        max_items: Some(100),
-                        marker: marker.clone(),
-                        ..ListFunctionsRequest::default()
-                    })
-                },
-                |err: &RusotoError<ListFunctionsError>| {
-                    log::debug!("lambda api error {}", err);
-                    match err {
-                        RusotoError::Service(ListFunctionsError::TooManyRequests(_)) => true,
-                        _ => false,
-                    }
-                },
-            )
-            .and_then(move |result| {
-                if let Some(marker) = result.next_marker.clone().filter(|s| !s.is_empty()) {
-                    return future::Either::A(lambdas(client, Some(marker)).map(|next| {
-                        result
-                            .functions
-                            .unwrap_or_default()
-                            .into_iter()
-                            .chain(next)
-                            .collect()
-                    }));
-                }
-                future::Either::B(future::ok(result.functions.unwrap_or_default()))
-            }),
-    )
-}
-
-fn tag_mappings(
-    client: ResourceGroupsTaggingApiClient,
-    pagination_token: Option<String>,
-    tag_filters: Option<Vec<TagFilter>>,
-) -> Box<Future<Item = Vec<ResourceTagMapping>, Error = RusotoError<GetResourcesError>> + Send> {
-    let client_inner = client.clone();
-    let tag_filters_inner = tag_filters.clone();
-    Box::new(
-        backoff()
-            .retry_if(
-                move || {
-                    client_inner.get_resources(GetResourcesInput {
-                        resource_type_filters: Some(vec!["lambda:function".into()]),
-                        resources_per_page: Some(50),
-                        pagination_token: pagination_token.clone(),
-                        tag_filters: tag_filters_inner.clone(),
-                        ..GetResourcesInput::default()
-                    })
-                },
-                |err: &RusotoError<GetResourcesError>| {
-                    log::debug!("tagging api error {}", err);
-                    match err {
-                        RusotoError::Service(GetResourcesError::InvalidParameter(_)) => true,
-                        _ => false,
-                    }
-                },
-            )
-            .and_then(move |result| {
-                if let Some(token) = result.pagination_token.clone().filter(|s| !s.is_empty()) {
-                    return future::Either::A(tag_mappings(client, Some(token), tag_filters).map(
-                        |next| {
-                            result
-                                .resource_tag_mapping_list
-                                .unwrap_or_default()
-                                .into_iter()
-                                .chain(next)
-                                .collect()
-                        },
-                    ));
-                }
-                future::Either::B(future::ok(
-                    result.resource_tag_mapping_list.unwrap_or_default(),
-                ))
-            }),
-    )
+    let interrupted = future::lazy(tokio_signal::ctrl_c)
+        .flatten_stream()
+        .into_future()
+        .then(|result| match result {
+            Ok((Some(()), _)) => future::Either::A(future::err(Error::Interrupted.into())),
+            _ => future::Either::B(future::empty()),
+        });
+    let future = future
+        .select(interrupted)
+        .map(|(item, _)| item)
+        .map_err(|(err, _)| err);
+    match deadline {
+        Some(deadline) => rt.block_on(
+            Timeout::new_at(future, deadline)
+                .map_err(|err| err.into_inner().unwrap_or_else(|| Error::TimedOut.into())),
+        ),
+        None => rt.block_on(future),
+    }
 }
 
-fn render_funcs(
-    funcs: &mut Vec<Func>,
-    sort: Sort,
-) {
-    funcs.sort_unstable_by(|a, b| match sort {
-        Sort::Name => a
-            .name()
-            .unwrap_or_default()
-            .cmp(&b.name().unwrap_or_default()),
-        Sort::CodeSize => a
-            .code_size()
-            .unwrap_or_default()
-            .cmp(&b.code_size().unwrap_or_default()),
-        Sort::Runtime => a
-            .runtime()
-            .unwrap_or_default()
-            .cmp(&b.runtime().unwrap_or_default()),
-    });
-    let mut writer = TabWriter::new(io::stdout());
-    for func in funcs {
-        drop(writeln!(
-            &mut writer,
-            "{}\t{}\t{}",
-            func.config.function_name.as_ref().unwrap(),
-            func.config.runtime.as_ref().unwrap(),
-            func.human_size()
-        ));
+/// Name the part of a command that needs the AWS APIs beyond listing lambdas, which a
+/// fixture or snapshot file can't stand in for
+fn fixture_unsupported(command: &Command) -> Option<&'static str> {
+    match command {
+        Command::Tags { .. } => Some("tags"),
+        Command::Aliases { .. } => Some("aliases"),
+        Command::Versions { .. } => Some("versions"),
+        Command::Storage { .. } => Some("storage"),
+        Command::StaleLayers { .. } => Some("stale-layers"),
+        Command::PurgeVersions { .. } => Some("purge-versions"),
+        Command::Show { .. } => Some("show"),
+        Command::List {
+            all_regions: true, ..
+        } => Some("list --all-regions"),
+        Command::List {
+            concurrency: true, ..
+        } => Some("list --concurrency"),
+        _ => None,
     }
-    drop(writer.flush())
 }
 
-fn render_tags(tags: BTreeSet<String>) {
-    for tag in tags {
-        println!("{}", tag)
+/// An extra file to render results to, in the format named by its extension
+#[derive(Debug, PartialEq)]
+struct Sink {
+    path: PathBuf,
+    output: Output,
+}
+
+fn parse_sink(path: &str) -> Result<Sink, String> {
+    let path = PathBuf::from(path);
+    match Output::from_extension(&path) {
+        Some(output) => Ok(Sink { path, output }),
+        None => Err(format!(
+            "can't tell the format of {} from its extension, expected .txt, .csv, .tsv, .yaml, .yml, .md, .prom or .jsonl",
+            path.display()
+        )),
     }
 }
 
-fn credentials() -> ChainProvider {
-    let mut chain = ChainProvider::new();
-    chain.set_timeout(Duration::from_millis(200));
-    chain
+/// Open the destination for rendered output, locked stdout unless a file path is provided
+fn output(path: Option<&PathBuf>) -> io::Result<Box<dyn Write>> {
+    match path {
+        Some(path) => Ok(Box::new(DeferredFile::create(path)?)),
+        None => Ok(Box::new(io::stdout().lock())),
+    }
 }
 
-fn lambda_client() -> LambdaClient {
-    LambdaClient::new_with(
-        HttpClient::new().expect("failed to create request dispatcher"),
-        credentials(),
-        Default::default(),
-    )
+/// A file that collects what is rendered to it and only replaces `path` on flush, by way of
+/// its temporary file renamed into place, so a run that fails, times out or is interrupted
+/// before rendering anything leaves the previous contents of `path` untouched
+struct DeferredFile {
+    path: PathBuf,
+    contents: Vec<u8>,
+    dirty: bool,
 }
 
-fn backoff() -> Strategy {
-    Strategy::exponential(Duration::from_millis(100))
-        .with_max_retries(15)
-        .with_jitter(true)
+impl DeferredFile {
+    /// Check up front that `path` can be written, without touching it
+    fn create(path: &Path) -> io::Result<DeferredFile> {
+        check_writable(path)?;
+        Ok(DeferredFile {
+            path: path.to_path_buf(),
+            contents: Vec::new(),
+            dirty: false,
+        })
+    }
 }
 
-fn tags_client() -> ResourceGroupsTaggingApiClient {
-    ResourceGroupsTaggingApiClient::new_with(
-        HttpClient::new().expect("failed to create request dispatcher"),
-        credentials(),
-        Default::default(),
-    )
+impl Write for DeferredFile {
+    fn write(
+        &mut self,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        self.contents.extend_from_slice(buf);
+        self.dirty = true;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let temp = temp_path(&self.path);
+        if let Err(err) =
+            fs::write(&temp, &self.contents).and_then(|_| fs::rename(&temp, &self.path))
+        {
+            drop(fs::remove_file(&temp));
+            return Err(err);
+        }
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for DeferredFile {
+    fn drop(&mut self) {
+        drop(self.flush())
+    }
+}
+
+/// Return the temporary file beside `path` that it is written to before being renamed into place
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+/// Check up front that `path` can be written by creating and removing its temporary file,
+/// leaving any existing file at `path` untouched
+fn check_writable(path: &Path) -> io::Result<()> {
+    let temp = temp_path(path);
+    File::create(&temp)?;
+    fs::remove_file(&temp)
+}
+
+/// Write a snapshot of `funcs` to a temporary file beside `path`, then rename it into place,
+/// so readers never see a partial snapshot and a failed write keeps the previous one
+fn save_snapshot(
+    path: &Path,
+    funcs: &[Func],
+) -> io::Result<()> {
+    let temp = temp_path(path);
+    let written = File::create(&temp).and_then(|mut file| {
+        write_snapshot(&mut file, funcs, Utc::now())?;
+        file.flush()
+    });
+    match written.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            drop(fs::remove_file(&temp));
+            Err(err)
+        }
+    }
 }
 
 fn main() {
-    env_logger::init();
+    let started = Instant::now();
     let mut rt = Runtime::new().expect("failed to initialize runtime");
-    let result = match Options::from_args() {
-        Options::Tags => {
-            let tags = tag_mappings(tags_client(), Default::default(), None).map_err(Error::from);
-            let names = tags.map(|mappings| {
-                mappings.iter().fold(BTreeSet::new(), |mut names, mapping| {
-                    for tag in mapping.tags.clone().unwrap_or_default() {
-                        names.insert(tag.key.clone());
-                    }
-                    names
-                })
+    let Options {
+        region,
+        config,
+        profile,
+        role_arn,
+        role_session_name,
+        lambda_page_size,
+        tags_page_size,
+        max_retries,
+        max_concurrency,
+        backoff_ms,
+        credential_timeout_ms,
+        min_credential_ttl,
+        timeout,
+        units,
+        verbose,
+        output_file,
+        no_progress,
+        error_format,
+        show_region,
+        fips,
+        fixture,
+        snapshot_in,
+        mut command,
+    } = Options::from_args();
+    init_logging(verbose);
+    let config = match Config::load(config.as_ref()) {
+        Ok(config) => config,
+        Err(err) => report(Error::Config(err), error_format),
+    };
+    if let Err(err) = command.load_tags_file() {
+        report(Error::TagsFile(err), error_format)
+    }
+    let lambda_page_size = lambda_page_size.or(config.lambda_page_size).unwrap_or(100);
+    let tags_page_size = tags_page_size.or(config.tags_page_size).unwrap_or(50);
+    let units = units.or(config.units).unwrap_or(Units::Conventional);
+    let mut out = match output(output_file.as_ref()) {
+        Ok(out) => out,
+        Err(err) => report(
+            Error::OutputFile(output_file.unwrap_or_default().display().to_string(), err),
+            error_format,
+        ),
+    };
+    let retries = Retries {
+        max: max_retries,
+        delay: Duration::from_millis(backoff_ms),
+        limiter: Limiter::new(max_concurrency),
+    };
+    let profile = profile.or(config.profile);
+    let (region, region_source) = match resolve_region(region, config.region, profile.as_deref()) {
+        Ok(resolved) => resolved,
+        Err(name) => report(Error::InvalidRegion(name), error_format),
+    };
+    if show_region {
+        eprintln!("region: {} (from {})", region.name(), region_source);
+    } else {
+        log::info!("querying region {} (from {})", region.name(), region_source);
+    }
+    let credential_timeout = match credential_timeout_ms {
+        0 => None,
+        millis => Some(Duration::from_millis(millis)),
+    };
+    let credentials = match role_arn {
+        Some(role_arn) => assume_role(
+            credentials(profile, credential_timeout),
+            region.clone(),
+            role_arn,
+            role_session_name,
+        ),
+        None => credentials(profile, credential_timeout),
+    };
+    let region = match (fips, region_source) {
+        (false, _) => region,
+        (true, RegionSource::Default) => report(Error::FipsRegionRequired, error_format),
+        (true, _) => match fips_region(region) {
+            Ok(region) => region,
+            Err(name) => report(Error::Fips(name), error_format),
+        },
+    };
+    let offline = fixture.is_some() || snapshot_in.is_some();
+    if offline {
+        if let Some(name) = fixture_unsupported(&command) {
+            report(Error::FixtureUnsupported(name), error_format)
+        }
+    }
+    let source: Arc<dyn Source> = match (fixture, snapshot_in) {
+        (Some(path), _) => match fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| json.parse::<Fixture>())
+        {
+            Ok(fixture) => Arc::new(fixture),
+            Err(err) => report(
+                Error::Fixture(path.display().to_string(), err),
+                error_format,
+            ),
+        },
+        (None, Some(path)) => match fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| json.parse::<Snapshot>())
+        {
+            Ok(snapshot) => {
+                eprintln!("using snapshot captured at {}", snapshot.captured_at);
+                Arc::new(snapshot.fixture)
+            }
+            Err(err) => report(
+                Error::Snapshot(path.display().to_string(), err),
+                error_format,
+            ),
+        },
+        (None, None) => Arc::new(Live {
+            credentials: credentials.clone(),
+            region: region.clone(),
+            lambda_page_size,
+            tags_page_size,
+            retries: retries.clone(),
+        }),
+    };
+    let deadline = timeout.map(|secs| started + Duration::from_secs(secs));
+    if !offline && !matches!(command, Command::Completions { .. }) {
+        // providers cache what they resolve, so the run reuses these credentials
+        let resolved = credentials.credentials().map_err(Error::Credentials);
+        match block_on(&mut rt, deadline, resolved).map(|creds| credential_ttl(&creds, Utc::now()))
+        {
+            Ok(Some(ttl)) => match min_credential_ttl {
+                Some(min) if ttl < min as i64 => {
+                    report(Error::CredentialsExpiring(ttl), error_format)
+                }
+                None if ttl < MIN_CREDENTIAL_TTL_SECS => log::info!(
+                    "credentials expire in {}s, a long run may fail before it finishes",
+                    ttl
+                ),
+                _ => log::debug!("credentials expire in {}s", ttl),
+            },
+            Ok(None) => (),
+            Err(err) => report(err, error_format),
+        }
+    }
+    let show_progress = !no_progress && atty::is(atty::Stream::Stderr);
+    let progress = Progress::new(show_progress);
+    let spinner = progress.clone();
+    let result = match command {
+        Command::Completions { shell } => {
+            let shell = shell
+                .parse()
+                .expect("shell is one of clap's possible values");
+            Options::clap().gen_completions_to("lambstock", shell, &mut out);
+            Ok(())
+        }
+        Command::Tags { values } => {
+            let tags = progress.clone().finish_after(tag_mappings(
+                tags_client(credentials, region),
+                None,
+                tags_page_size,
+                retries,
+                progress,
+            ));
+            block_on(&mut rt, deadline, tags).map(|mappings| {
+                let names = tag_names(&mappings, values);
+                if values {
+                    render_tag_values(
+                        &mut out,
+                        names
+                            .into_iter()
+                            .filter_map(|(key, value)| Some((key, value?)))
+                            .collect(),
+                    )
+                } else {
+                    render_tags(&mut out, names)
+                }
+            })
+        }
+        Command::Runtimes => block_on(&mut rt, deadline, source.funcs(Vec::new(), progress))
+            .map(|funcs| render_runtimes(&mut out, runtime_counts(&funcs))),
+        Command::Count { tags, .. } => block_on(&mut rt, deadline, source.funcs(tags, progress))
+            .map(|funcs| drop(writeln!(out, "{}", funcs.len()))),
+        Command::Diff { left, right } => {
+            let left_label = tag_filter_label(&left);
+            let right_label = tag_filter_label(&right);
+            let left = source.region_funcs(left, progress.clone());
+            let right = source.region_funcs(right, progress.clone());
+            block_on(&mut rt, deadline, progress.finish_after(left.join(right))).map(
+                |(left, right)| {
+                    let mut diff = FuncDiff::new(left, right);
+                    sort_funcs(&mut diff.left, Sort::Name, false, false, false);
+                    sort_funcs(&mut diff.both, Sort::Name, false, false, false);
+                    sort_funcs(&mut diff.right, Sort::Name, false, false, false);
+                    render_diff(&mut out, &diff, &left_label, &right_label)
+                },
+            )
+        }
+        Command::Aliases { tags, .. } => {
+            let alias_credentials = credentials.clone();
+            let alias_region = region.clone();
+            let alias_retries = retries.clone();
+            let aliases = source.funcs(tags, progress).and_then(move |mut funcs| {
+                sort_funcs(&mut funcs, Sort::Name, false, false, false);
+                with_aliases(
+                    alias_credentials,
+                    alias_region,
+                    funcs,
+                    alias_retries,
+                    CONCURRENCY_PARALLELISM,
+                )
             });
-            rt.block_on(names.map(render_tags))
+            block_on(&mut rt, deadline, aliases).map(|funcs| render_aliases(&mut out, &funcs))
         }
-        Options::List { tags, sort } => {
-            let tag_mappings = tag_mappings(tags_client(), Default::default(), Some(filters(tags)))
-                .map_err(Error::from);
-
-            let lambdas = lambdas(lambda_client(), Default::default()).map_err(Error::from);
-            let filtered = tag_mappings.join(lambdas).map(|(tags, lambdas)| {
-                let lookup: HashMap<String, FunctionConfiguration> = lambdas
-                    .into_iter()
-                    .map(|config| (config.function_arn.clone().unwrap_or_default(), config))
-                    .collect();
-                tags.into_iter().fold(Vec::new(), |mut result, mapping| {
-                    if let Some(config) = lookup.get(&mapping.resource_arn.unwrap_or_default()) {
-                        result.push(Func {
-                            tags: mapping.tags.unwrap_or_default(),
-                            config: config.clone(),
-                        });
+        Command::Versions { tags, color, .. } => {
+            colored::control::set_override(color.enabled());
+            let version_credentials = credentials.clone();
+            let version_region = region.clone();
+            let version_retries = retries.clone();
+            let versions = source.funcs(tags, progress).and_then(move |funcs| {
+                with_versions(
+                    version_credentials,
+                    version_region,
+                    funcs,
+                    version_retries,
+                    CONCURRENCY_PARALLELISM,
+                )
+            });
+            block_on(&mut rt, deadline, versions).map(|mut funcs| {
+                funcs.sort_by(|(a, a_versions), (b, b_versions)| {
+                    b_versions
+                        .count
+                        .cmp(&a_versions.count)
+                        .then_with(|| a.name().cmp(&b.name()))
+                });
+                render_versions(&mut out, &funcs, units)
+            })
+        }
+        Command::Storage { limit_gb } => {
+            let version_credentials = credentials.clone();
+            let version_region = region.clone();
+            let version_retries = retries.clone();
+            let versions = source.funcs(Vec::new(), progress).and_then(move |funcs| {
+                with_versions(
+                    version_credentials,
+                    version_region,
+                    funcs,
+                    version_retries,
+                    CONCURRENCY_PARALLELISM,
+                )
+            });
+            block_on(&mut rt, deadline, versions).map(|funcs| {
+                render_storage(
+                    &mut out,
+                    &funcs,
+                    limit_gb.unwrap_or(CODE_STORAGE_QUOTA_GB),
+                    units,
+                )
+            })
+        }
+        Command::StaleLayers { tags, .. } => {
+            let layer_credentials = credentials.clone();
+            let layer_region = region.clone();
+            let layer_retries = retries.clone();
+            let stale = source.funcs(tags, progress).and_then(move |mut funcs| {
+                sort_funcs(&mut funcs, Sort::Name, false, false, false);
+                stale_layers(
+                    layer_credentials,
+                    layer_region,
+                    funcs,
+                    layer_retries,
+                    CONCURRENCY_PARALLELISM,
+                )
+            });
+            block_on(&mut rt, deadline, stale).map(|stale| render_stale_layers(&mut out, &stale))
+        }
+        Command::PurgeVersions {
+            tags, keep, apply, ..
+        } => {
+            let purge_credentials = credentials.clone();
+            let purge_region = region.clone();
+            let purge_retries = retries.clone();
+            let purges = source
+                .funcs(tags, progress)
+                .and_then(move |mut funcs| {
+                    sort_funcs(&mut funcs, Sort::Name, false, false, false);
+                    with_purgeable_versions(
+                        purge_credentials,
+                        purge_region,
+                        funcs,
+                        keep,
+                        purge_retries,
+                        CONCURRENCY_PARALLELISM,
+                    )
+                })
+                .map(|purges| {
+                    purges
+                        .into_iter()
+                        .filter(|(_, versions)| !versions.is_empty())
+                        .collect::<Vec<_>>()
+                });
+            match block_on(&mut rt, deadline, purges) {
+                Ok(purges) if apply => block_on(
+                    &mut rt,
+                    deadline,
+                    purge_versions(
+                        credentials,
+                        region,
+                        &purges,
+                        retries,
+                        CONCURRENCY_PARALLELISM,
+                    ),
+                )
+                .map(|failures| {
+                    render_purge(&mut out, &purges, Some(failures), units);
+                    if failures > 0 {
+                        drop(out.flush());
+                        exit(1)
+                    }
+                }),
+                Ok(purges) => {
+                    render_purge(&mut out, &purges, None, units);
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            }
+        }
+        Command::Check { required } => {
+            block_on(&mut rt, deadline, source.funcs(Vec::new(), progress)).map(|mut funcs| {
+                sort_funcs(&mut funcs, Sort::Name, false, false, false);
+                if render_missing_tags(&mut out, &funcs, &required) > 0 {
+                    drop(out.flush());
+                    exit(2)
+                }
+            })
+        }
+        Command::Stats {
+            tags,
+            tags_file: _,
+            cost,
+            price_per_gb,
+        } => block_on(&mut rt, deadline, source.funcs(tags, progress)).map(|funcs| {
+            render_stats(&mut out, &funcs, units);
+            if cost {
+                render_cost(
+                    &mut out,
+                    &funcs,
+                    price_per_gb.unwrap_or(STORAGE_PRICE_PER_GB),
+                )
+            }
+        }),
+        Command::Show {
+            name,
+            env,
+            show_values,
+        } => {
+            let func = function(lambda_client(credentials, region), name.clone(), retries)
+                .map_err(move |err| match err {
+                    RusotoError::Service(GetFunctionError::ResourceNotFound(_)) => {
+                        Error::NotFound(name)
                     }
-                    result
+                    err => Error::from(err),
                 })
-            });
-            rt.block_on(filtered.map(|mut funcs| render_funcs(&mut funcs, sort)))
+                .map(Func::from);
+            block_on(&mut rt, deadline, func)
+                .map(|func| render_func_detail(&mut out, &func, env, show_values, units))
+        }
+        Command::List {
+            tags,
+            tags_file: _,
+            tag_filter_mode,
+            sort,
+            reverse,
+            natural_sort,
+            ignore_case,
+            limit,
+            output,
+            name,
+            summary,
+            deprecated_only,
+            go_migration,
+            near_limit,
+            near_limit_pct,
+            outliers,
+            outlier_timeout,
+            outlier_memory,
+            color,
+            mut columns,
+            with_tags,
+            tags_width,
+            padding,
+            min_width,
+            no_header,
+            template,
+            concurrency,
+            log_group,
+            layers,
+            vpc,
+            problems_only,
+            min_size,
+            max_size,
+            sha,
+            include_arns_from_file,
+            report_missing_arns,
+            min_timeout,
+            search,
+            select,
+            group_by,
+            sum_by,
+            fail_on_empty,
+            also_write,
+            snapshot_out,
+            exclude_tags,
+            modified_before,
+            modified_after,
+            untagged,
+            tag_keys,
+            all_regions,
+            region_concurrency,
+            tracing,
+            tracing_disabled,
+            runtime_family,
+            has_env,
+            no_env,
+            watch,
+            cost,
+            price_per_gb,
+        } => {
+            colored::control::set_override(color.enabled());
+            let sort = sort.or(config.sort).unwrap_or(Sort::Name);
+            if with_tags && !columns.contains(&Column::Tags) {
+                if columns.is_empty() {
+                    columns = Column::defaults();
+                }
+                columns.push(Column::Tags);
+            }
+            if concurrency && !columns.contains(&Column::Concurrency) {
+                if columns.is_empty() {
+                    columns = Column::defaults();
+                }
+                columns.push(Column::Concurrency);
+            }
+            if log_group && !columns.contains(&Column::LogGroup) {
+                if columns.is_empty() {
+                    columns = Column::defaults();
+                }
+                columns.push(Column::LogGroup);
+            }
+            let (tags, any_tags) = match tag_filter_mode {
+                TagFilterMode::All => (tags, Vec::new()),
+                TagFilterMode::Any => (Vec::new(), split_tag_values(tags)),
+            };
+            let fetch_concurrency = columns.contains(&Column::Concurrency)
+                || template
+                    .iter()
+                    .any(|template| template.columns().contains(&Column::Concurrency));
+            if all_regions && !columns.contains(&Column::Region) {
+                if columns.is_empty() {
+                    columns = Column::defaults();
+                }
+                columns.push(Column::Region);
+            }
+            let include_arns = match include_arns_from_file {
+                Some(path) => match fs::read_to_string(&path) {
+                    Ok(contents) => Some(parse_arn_lines(&contents)),
+                    Err(err) => report(
+                        Error::ArnsFile(format!("{}: {}", path.display(), err)),
+                        error_format,
+                    ),
+                },
+                None => None,
+            };
+            let pattern = match name.map(|name| Regex::new(&name)).transpose() {
+                Ok(pattern) => pattern,
+                Err(err) => report(Error::from(err), error_format),
+            };
+            if let Some(path) = &snapshot_out {
+                if let Err(err) = check_writable(path) {
+                    report(
+                        Error::OutputFile(path.display().to_string(), err),
+                        error_format,
+                    )
+                }
+            }
+            let fetch = move |progress: Progress| {
+                let funcs = if all_regions {
+                    future::Either::A(all_region_funcs(
+                        &Live {
+                            credentials: credentials.clone(),
+                            region: region.clone(),
+                            lambda_page_size,
+                            tags_page_size,
+                            retries: retries.clone(),
+                        },
+                        if fips {
+                            fips_partition_regions(&region)
+                        } else {
+                            LAMBDA_REGIONS.to_vec()
+                        },
+                        tags.clone(),
+                        region_concurrency,
+                        progress,
+                    ))
+                } else {
+                    future::Either::B(
+                        source
+                            .funcs(tags.clone(), progress)
+                            .map(|funcs| (funcs, Vec::new())),
+                    )
+                };
+                let concurrency_credentials = credentials.clone();
+                let concurrency_region = region.clone();
+                let concurrency_retries = retries.clone();
+                let pattern = pattern.clone();
+                let sha = sha.clone();
+                let include_arns = include_arns.clone();
+                let select = select.clone();
+                let search = search.clone();
+                let exclude_tags = exclude_tags.clone();
+                let any_tags = any_tags.clone();
+                let tag_keys = tag_keys.clone();
+                let snapshot_out = snapshot_out.clone();
+                funcs
+                    .and_then(move |(funcs, failures)| {
+                        let saved = match &snapshot_out {
+                            Some(path) => save_snapshot(path, &funcs)
+                                .map_err(|err| Error::OutputFile(path.display().to_string(), err)),
+                            None => Ok(()),
+                        };
+                        future::result(saved.map(|_| (funcs, failures)))
+                    })
+                    .map(move |(funcs, failures)| {
+                        if let (Some(arns), true) = (&include_arns, report_missing_arns) {
+                            let found = funcs.iter().filter_map(Func::arn).collect::<HashSet<_>>();
+                            for arn in arns.iter().filter(|arn| !found.contains(*arn)) {
+                                log::warn!("not found: {}", arn)
+                            }
+                        }
+                        let funcs = funcs
+                            .into_iter()
+                            .filter(|func| func.matches_name(pattern.as_ref()))
+                            .filter(|func| !deprecated_only || func.is_deprecated_runtime())
+                            .filter(|func| func.at_least_size(min_size))
+                            .filter(|func| func.at_most_size(max_size))
+                            .filter(|func| func.matches_sha256(sha.as_deref()))
+                            .filter(|func| func.in_arns(include_arns.as_ref()))
+                            .filter(|func| func.at_least_timeout(min_timeout))
+                            .filter(|func| func.matches_description(search.as_deref()))
+                            .filter(|func| select.iter().all(|select| select.matches(func)))
+                            .filter(|func| match tracing {
+                                Some(tracing) => func.tracing() == tracing,
+                                None => true,
+                            })
+                            .filter(|func| !tracing_disabled || func.tracing() != Tracing::Active)
+                            .filter(|func| match runtime_family {
+                                Some(family) => func.runtime_family() == Some(family),
+                                None => true,
+                            })
+                            .filter(|func| !exclude_tags.iter().any(|(k, v)| func.has_tag(k, v)))
+                            .filter(|func| {
+                                any_tags.is_empty()
+                                    || any_tags.iter().any(|(k, v)| func.has_tag(k, v))
+                            })
+                            .filter(|func| !untagged || func.tags.is_empty())
+                            .filter(|func| tag_keys.iter().all(|key| func.has_tag_key(key)))
+                            .filter(|func| !has_env || func.has_env_vars())
+                            .filter(|func| !no_env || !func.has_env_vars())
+                            .filter(|func| func.modified_between(modified_after, modified_before))
+                            .collect::<Vec<_>>();
+                        (funcs, failures)
+                    })
+                    .and_then(move |(funcs, failures)| {
+                        if fetch_concurrency {
+                            future::Either::A(
+                                with_concurrency(
+                                    concurrency_credentials,
+                                    concurrency_region,
+                                    funcs,
+                                    concurrency_retries,
+                                    CONCURRENCY_PARALLELISM,
+                                )
+                                .map(move |funcs| (funcs, failures)),
+                            )
+                        } else {
+                            future::Either::B(future::ok((funcs, failures)))
+                        }
+                    })
+            };
+            let mut sinks: Vec<(Box<dyn Write>, Output)> = Vec::new();
+            for sink in also_write {
+                match DeferredFile::create(&sink.path) {
+                    Ok(file) => sinks.push((Box::new(file), sink.output)),
+                    Err(err) => report(
+                        Error::OutputFile(sink.path.display().to_string(), err),
+                        error_format,
+                    ),
+                }
+            }
+            let render = |out: &mut Box<dyn Write>, output: Output, funcs: &mut Vec<Func>| {
+                let matched = funcs.len();
+                if let Some(group_by) = group_by {
+                    render_grouped(
+                        out,
+                        &group_funcs(funcs, group_by),
+                        &group_by.to_string(),
+                        "(none)",
+                        units,
+                    )
+                } else if let Some(key) = &sum_by {
+                    render_grouped(out, &group_funcs_by_tag(funcs, key), key, "untagged", units)
+                } else {
+                    sort_funcs(funcs, sort, reverse, natural_sort, ignore_case);
+                    if let Some(limit) = limit {
+                        if limit == 0 {
+                            return matched;
+                        }
+                        funcs.truncate(limit);
+                    }
+                    if layers {
+                        render_layers(out, funcs)
+                    } else if vpc {
+                        render_vpcs(out, funcs)
+                    } else if problems_only {
+                        render_problems(out, funcs)
+                    } else if go_migration {
+                        render_go_migration(out, funcs)
+                    } else if near_limit {
+                        render_near_limit(
+                            out,
+                            funcs,
+                            near_limit_pct.unwrap_or(NEAR_LIMIT_PCT),
+                            units,
+                        )
+                    } else if outliers {
+                        render_outliers(
+                            out,
+                            funcs,
+                            outlier_timeout.unwrap_or(OUTLIER_TIMEOUT_SECS),
+                            outlier_memory.unwrap_or(OUTLIER_MEMORY_MB),
+                        )
+                    } else if let Some(template) = &template {
+                        render_template(out, funcs, template, units)
+                    } else {
+                        render_funcs(
+                            out,
+                            funcs,
+                            output,
+                            &columns,
+                            units,
+                            TextLayout {
+                                header: !no_header,
+                                tags_width,
+                                padding,
+                                min_width,
+                            },
+                        )
+                    }
+                }
+                if summary {
+                    render_summary(out, funcs, units)
+                }
+                if cost {
+                    render_cost(out, funcs, price_per_gb.unwrap_or(STORAGE_PRICE_PER_GB))
+                }
+                matched
+            };
+            // files are data exports, so they always list functions in their own format, with the
+            // summary and cost lines only in text formats
+            let render_sink = |out: &mut Box<dyn Write>, output: Output, funcs: &mut Vec<Func>| {
+                sort_funcs(funcs, sort, reverse, natural_sort, ignore_case);
+                let limited = &funcs[..limit.unwrap_or(funcs.len()).min(funcs.len())];
+                if limit == Some(0) {
+                    return;
+                }
+                render_funcs(
+                    out,
+                    limited,
+                    output,
+                    &columns,
+                    units,
+                    TextLayout {
+                        header: !no_header,
+                        tags_width,
+                        padding,
+                        min_width,
+                    },
+                );
+                if let Output::Text | Output::Table = output {
+                    if summary {
+                        render_summary(out, limited, units)
+                    }
+                    if cost {
+                        render_cost(out, limited, price_per_gb.unwrap_or(STORAGE_PRICE_PER_GB))
+                    }
+                }
+            };
+            match watch {
+                None => {
+                    block_on(&mut rt, deadline, fetch(progress)).map(|(mut funcs, failures)| {
+                        for (region, err) in &failures {
+                            print_region_error(region, err, error_format)
+                        }
+                        if !sinks.is_empty() {
+                            colored::control::set_override(false);
+                            for (sink, sink_output) in &mut sinks {
+                                render_sink(sink, *sink_output, &mut funcs);
+                                drop(sink.flush());
+                            }
+                            colored::control::set_override(color.enabled());
+                        }
+                        let matched = render(&mut out, output, &mut funcs);
+                        if fail_on_empty && matched == 0 {
+                            drop(out.flush());
+                            exit(2)
+                        }
+                        if !failures.is_empty() {
+                            drop(out.flush());
+                            exit(3)
+                        }
+                    })
+                }
+                Some(seconds) => loop {
+                    // the deadline restarts with each refresh, and never cuts the wait between them short
+                    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs(secs));
+                    match block_on(&mut rt, deadline, fetch(Progress::new(show_progress))) {
+                        Err(Error::Interrupted) => break Ok(()),
+                        result => {
+                            drop(write!(out, "{}", CLEAR_SCREEN));
+                            match result {
+                                Ok((mut funcs, failures)) => {
+                                    for (region, err) in &failures {
+                                        print_region_error(region, err, error_format)
+                                    }
+                                    render(&mut out, output, &mut funcs);
+                                }
+                                Err(err) => print_error(&err, error_format),
+                            }
+                            drop(out.flush());
+                        }
+                    }
+                    let delay = Delay::new(Instant::now() + Duration::from_secs(seconds))
+                        .then(|_| future::ok::<_, Error>(()));
+                    match block_on(&mut rt, None, delay) {
+                        Err(Error::Interrupted) => break Ok(()),
+                        Err(err) => break Err(err),
+                        Ok(()) => (),
+                    }
+                },
+            }
         }
     };
     if let Err(err) = result {
-        for cause in Fail::iter_causes(&err) {
-            eprintln!("{}", cause);
-        }
-        exit(1)
+        spinner.finish();
+        report(err, error_format)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{filters, Func, FunctionConfiguration, TagFilter};
+    use super::{
+        block_on, check_writable, error_json, fixture_unsupported, future, log_level,
+        parse_arn_lines, parse_date, parse_lambda_page_size, parse_region_arg, parse_sink,
+        parse_size, parse_tags_lines, parse_tags_page_size, save_snapshot, tag_filter_label,
+        Command, Config, DeferredFile, Duration, Error, Instant, Options, Output, Region, Runtime,
+        RusotoError, Sort, TagFilterMode, Units,
+    };
+    use lambstock::filters;
+    use std::{env, fs, io::Write, path::PathBuf, process};
+    use structopt::StructOpt;
+    #[test]
+    fn cli_tag_value_keeps_equals_signs() {
+        let tags = match Options::from_iter(&["lambstock", "list", "--tag", "url=https://x?a=b"])
+            .command
+        {
+            Command::List { tags, .. } => tags,
+            _ => panic!("expected list"),
+        };
+        assert_eq!(vec![("url".to_string(), "https://x?a=b".to_string())], tags);
+        let filters = filters(tags);
+        assert_eq!(Some("url".to_string()), filters[0].key);
+        assert_eq!(Some(vec!["https://x?a=b".to_string()]), filters[0].values)
+    }
     #[test]
-    fn func_human_size() {
+    fn cli_size_suffixes() {
+        assert_eq!(Ok(512), parse_size("512"));
+        assert_eq!(Ok(10 * 1024 * 1024), parse_size("10MB"));
+        assert_eq!(Ok(10 * 1024 * 1024), parse_size("10MiB"));
+        assert_eq!(Ok(1536), parse_size("1.5kb"));
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("MB").is_err())
+    }
+    #[test]
+    fn cli_date_format() {
+        assert!(parse_date("2019-06-28").is_ok());
+        assert!(parse_date("06/28/2019").is_err())
+    }
+    #[test]
+    fn cli_also_write_infers_format() {
         assert_eq!(
-            "1 KB",
-            Func {
-                config: FunctionConfiguration {
-                    code_size: Some(1024),
-                    ..Default::default()
-                },
-                ..Default::default()
+            Ok(Output::Csv),
+            parse_sink("out/report.csv").map(|sink| sink.output)
+        );
+        assert!(parse_sink("report.xlsx").is_err());
+        assert!(parse_sink("report.json").is_err());
+        match Options::from_iter(&[
+            "lambstock",
+            "list",
+            "--also-write",
+            "report.jsonl",
+            "--also-write",
+            "report.md",
+        ])
+        .command
+        {
+            Command::List { also_write, .. } => assert_eq!(
+                vec![
+                    (PathBuf::from("report.jsonl"), Output::Jsonl),
+                    (PathBuf::from("report.md"), Output::Markdown)
+                ],
+                also_write
+                    .into_iter()
+                    .map(|sink| (sink.path, sink.output))
+                    .collect::<Vec<_>>()
+            ),
+            _ => panic!("expected list"),
+        }
+    }
+    #[test]
+    fn cli_tag_filter_mode_defaults_to_all() {
+        let mode = |args: &[&str]| match Options::from_iter(args).command {
+            Command::List {
+                tag_filter_mode, ..
+            } => tag_filter_mode,
+            _ => panic!("expected list"),
+        };
+        assert_eq!(TagFilterMode::All, mode(&["lambstock", "list"]));
+        assert_eq!(
+            TagFilterMode::Any,
+            mode(&["lambstock", "list", "--tag-filter-mode", "any"])
+        )
+    }
+    #[test]
+    fn cli_min_credential_ttl() {
+        let min_credential_ttl = |args: &[&str]| Options::from_iter(args).min_credential_ttl;
+        assert_eq!(None, min_credential_ttl(&["lambstock", "list"]));
+        assert_eq!(
+            Some(3600),
+            min_credential_ttl(&["lambstock", "list", "--min-credential-ttl", "3600"])
+        );
+        assert!(
+            Options::from_iter_safe(&["lambstock", "--min-credential-ttl", "soon", "list"])
+                .is_err()
+        )
+    }
+    #[test]
+    fn cli_region_concurrency() {
+        let region_concurrency = |args: &[&str]| match Options::from_iter(args).command {
+            Command::List {
+                region_concurrency, ..
+            } => region_concurrency,
+            _ => panic!("expected list"),
+        };
+        assert_eq!(
+            4,
+            region_concurrency(&["lambstock", "list", "--all-regions"])
+        );
+        assert_eq!(
+            8,
+            region_concurrency(&[
+                "lambstock",
+                "list",
+                "--all-regions",
+                "--region-concurrency",
+                "8"
+            ])
+        );
+        assert!(
+            Options::from_iter_safe(&["lambstock", "list", "--region-concurrency", "0"]).is_err()
+        )
+    }
+    #[test]
+    fn cli_sort_size_alias() {
+        match Options::from_iter(&["lambstock", "list", "--sort", "size"]).command {
+            Command::List { sort, .. } => assert_eq!(Some(Sort::CodeSize), sort),
+            _ => panic!("expected list"),
+        }
+    }
+    #[test]
+    fn cli_fixture_covers_listing() {
+        let unsupported = |args: &[&str]| fixture_unsupported(&Options::from_iter(args).command);
+        assert_eq!(None, unsupported(&["lambstock", "list", "--tag", "team=a"]));
+        assert_eq!(None, unsupported(&["lambstock", "count"]));
+        assert_eq!(
+            Some("list --all-regions"),
+            unsupported(&["lambstock", "list", "--all-regions"])
+        );
+        assert_eq!(Some("aliases"), unsupported(&["lambstock", "aliases"]));
+        assert_eq!(
+            Some(PathBuf::from("demo.json")),
+            Options::from_iter(&["lambstock", "count", "--fixture", "demo.json"]).fixture
+        );
+        assert!(Options::from_iter_safe(&[
+            "lambstock",
+            "count",
+            "--fixture",
+            "demo.json",
+            "--snapshot-in",
+            "snapshot.json"
+        ])
+        .is_err())
+    }
+    #[test]
+    fn cli_region_is_global() {
+        assert_eq!(
+            Some(Region::UsWest2),
+            Options::from_iter(&["lambstock", "list", "--region", "us-west-2"]).region
+        );
+        assert_eq!(Ok(Region::EuWest1), parse_region_arg(" EU-West-1 "));
+        assert!(parse_region_arg("us-east-99")
+            .unwrap_err()
+            .contains("invalid region us-east-99"))
+    }
+    #[test]
+    fn cli_page_size_within_limits() {
+        assert_eq!(Ok(25), parse_lambda_page_size("25"));
+        assert!(parse_lambda_page_size("0").is_err());
+        assert!(parse_lambda_page_size("101").is_err());
+        assert!(parse_tags_page_size("many").is_err())
+    }
+    #[test]
+    fn cli_verbosity_levels() {
+        let verbose = |args: &[&str]| Options::from_iter(args).verbose;
+        assert_eq!(0, verbose(&["lambstock", "list"]));
+        assert_eq!(2, verbose(&["lambstock", "list", "-vv"]));
+        assert_eq!(log::LevelFilter::Warn, log_level(0));
+        assert_eq!(log::LevelFilter::Debug, log_level(2))
+    }
+    #[test]
+    fn cli_zero_retries_allowed() {
+        let options = Options::from_iter(&["lambstock", "list", "--max-retries", "0"]);
+        assert_eq!(0, options.max_retries);
+        assert_eq!(100, options.backoff_ms)
+    }
+    #[test]
+    fn errors_as_json() {
+        assert_eq!(
+            r#"{"causes":[],"error":"function not found: checkout"}"#,
+            error_json(&Error::NotFound("checkout".into())).to_string()
+        );
+        let err = Error::Tags(RusotoError::Validation("bad page size".into()));
+        let json = error_json(&err);
+        assert_eq!(1, json["causes"].as_array().unwrap().len());
+        assert_eq!(json["error"], json["causes"][0]);
+        assert!(json.get("request_id").is_none());
+        let err = Error::AllRegions(vec![
+            ("us-east-1".into(), Error::TimedOut),
+            ("eu-west-1".into(), Error::TimedOut),
+        ]);
+        assert_eq!(
+            r#"{"causes":[],"error":"failed to list lambdas in every region"}"#,
+            error_json(&err).to_string()
+        )
+    }
+    #[test]
+    fn cli_diff_tag_filters() {
+        let options =
+            Options::from_iter(&["lambstock", "diff", "--left", "team=a", "--right", "team=b"]);
+        match options.command {
+            Command::Diff { left, right } => {
+                assert_eq!("team=a", tag_filter_label(&left));
+                assert_eq!("team=b", tag_filter_label(&right))
             }
-            .human_size()
+            _ => panic!("expected diff"),
+        }
+        assert_eq!("all functions", tag_filter_label(&[]))
+    }
+    #[test]
+    fn cli_watch_conflicts_with_fail_on_empty() {
+        assert!(Options::from_iter_safe(&["lambstock", "list", "--watch", "30"]).is_ok());
+        assert!(
+            Options::from_iter_safe(&["lambstock", "list", "--watch", "30", "--fail-on-empty"])
+                .is_err()
         )
     }
     #[test]
-    fn cli_tags_to_filters() {
-        let filters = filters(vec![("foo".into(), "bar".into())]);
+    fn save_snapshot_replaces_in_place() {
+        let path = env::temp_dir().join(format!("lambstock-snapshot-{}.json", process::id()));
+        fs::write(&path, "previous").unwrap();
+        save_snapshot(&path, &[]).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        drop(fs::remove_file(&path));
+        assert!(saved.contains("\"functions\": []"));
+        assert!(!PathBuf::from(format!("{}.tmp", path.display())).exists());
+        let missing = env::temp_dir().join(format!("lambstock-missing-{}.json", process::id()));
+        check_writable(&missing).unwrap();
+        assert!(!missing.exists());
+        assert!(check_writable(&missing.join("nested").join("snapshot.json")).is_err())
+    }
+    #[test]
+    fn deferred_file_replaces_on_flush() {
+        let path = env::temp_dir().join(format!("lambstock-report-{}.csv", process::id()));
+        fs::write(&path, "previous").unwrap();
+        drop(DeferredFile::create(&path).unwrap());
+        assert_eq!("previous", fs::read_to_string(&path).unwrap());
+        let mut file = DeferredFile::create(&path).unwrap();
+        write!(file, "name").unwrap();
+        assert_eq!("previous", fs::read_to_string(&path).unwrap());
+        file.flush().unwrap();
+        write!(file, ",size").unwrap();
+        file.flush().unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        drop(fs::remove_file(&path));
+        assert_eq!("name,size", written)
+    }
+    #[test]
+    fn cli_watch_with_timeout() {
+        let options =
+            Options::from_iter(&["lambstock", "--timeout", "60", "list", "--watch", "30"]);
+        assert_eq!(Some(60), options.timeout);
+        match options.command {
+            Command::List { watch, .. } => assert_eq!(Some(30), watch),
+            _ => panic!("expected list"),
+        }
+    }
+    #[test]
+    fn cli_credential_timeout() {
+        let timeout = |args: &[&str]| Options::from_iter(args).credential_timeout_ms;
+        assert_eq!(200, timeout(&["lambstock", "list"]));
+        assert_eq!(
+            0,
+            timeout(&["lambstock", "list", "--credential-timeout-ms", "0"])
+        )
+    }
+    #[test]
+    fn cli_tags_file_lines() {
+        assert_eq!(
+            vec![
+                ("team".to_string(), "payments".to_string()),
+                ("env".to_string(), "prod".to_string())
+            ],
+            parse_tags_lines("# audit\nteam=payments\n\n  env=prod  \n").unwrap()
+        );
+        let err = parse_tags_lines("team=payments\nenv\n").unwrap_err();
+        assert!(err.starts_with("line 2: "))
+    }
+    #[test]
+    fn cli_arns_file_lines() {
+        let arns = parse_arn_lines(
+            "# audit\narn:aws:lambda:us-east-1:123456789012:function:api\n\n  arn:aws:lambda:us-east-1:123456789012:function:worker  \n",
+        );
+        assert_eq!(
+            vec![
+                "arn:aws:lambda:us-east-1:123456789012:function:api",
+                "arn:aws:lambda:us-east-1:123456789012:function:worker"
+            ],
+            arns.iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        assert!(Options::from_iter_safe(&["lambstock", "list", "--report-missing-arns"]).is_err())
+    }
+    #[test]
+    fn cli_config_file_defaults() {
+        let config: Config = toml::from_str(
+            "region = \"us-west-2\"\nsort = \"codesize\"\nlambda-page-size = 25\nunits = \"binary\"",
+        )
+        .expect("failed to parse config");
+        assert_eq!(Some(Region::UsWest2), config.region);
+        assert_eq!(Some(Sort::CodeSize), config.sort);
+        assert_eq!(Some(25), config.lambda_page_size);
+        assert_eq!(Some(Units::Binary), config.units);
+        assert_eq!(None, config.profile);
+        assert!(toml::from_str::<Config>("tags-page-size = 500").is_err());
+        assert!(toml::from_str::<Config>("sort = \"color\"").is_err())
+    }
+    #[test]
+    fn cli_block_on_without_interrupt() {
+        let mut rt = Runtime::new().expect("failed to initialize runtime");
+        assert_eq!(
+            Some(1),
+            block_on(&mut rt, None, future::ok::<_, Error>(1)).ok()
+        )
+    }
+    #[test]
+    fn cli_block_on_past_deadline() {
+        let mut rt = Runtime::new().expect("failed to initialize runtime");
+        let deadline = Instant::now() + Duration::from_millis(10);
+        match block_on(&mut rt, Some(deadline), future::empty::<(), Error>()) {
+            Err(Error::TimedOut) => (),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert_eq!(
+            Some(1),
+            block_on(&mut rt, Some(deadline), future::ok::<_, Error>(1)).ok()
+        )
+    }
+    #[test]
+    fn cli_role_session_name_default() {
+        let options =
+            Options::from_iter(&["lambstock", "list", "--role-arn", "arn:aws:iam::1:role/r"]);
+        assert_eq!(Some("arn:aws:iam::1:role/r".into()), options.role_arn);
+        assert_eq!("lambstock", options.role_session_name)
+    }
+    #[test]
+    fn cli_completions_shells() {
+        assert!(Options::from_iter_safe(&["lambstock", "completions", "zsh"]).is_ok());
+        assert!(Options::from_iter_safe(&["lambstock", "completions", "tcsh"]).is_err())
+    }
+    #[test]
+    fn cli_config_file_missing() {
+        assert!(Config::load(Some(&PathBuf::from("/nonexistent/lambstock.toml"))).is_err());
         assert_eq!(
-            filters,
-            vec![TagFilter {
-                key: Some("foo".into()),
-                values: Some(vec!["bar".into()]),
-            }]
+            None,
+            Options::from_iter(&["lambstock", "list"]).lambda_page_size
         )
     }
 }